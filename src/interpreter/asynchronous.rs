@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{error::RuntimeError, limits::Limits, options::Options, status::Status, Interpreter};
+use crate::parser::Program;
+
+/// Default number of instructions executed before giving other tasks a chance to run.
+pub const DEFAULT_YIELD_EVERY: usize = 10_000;
+
+/// Input for the inner interpreter, which only has the bytes that
+/// were already received and asks for more by returning `WouldBlock`.
+#[derive(Default)]
+struct PendingInput {
+    buffer: VecDeque<u8>,
+    /// set once the actual input has nothing more to give
+    end_of_input: bool,
+}
+
+impl Read for PendingInput {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer.is_empty() && !self.end_of_input {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.buffer.read(buf)
+    }
+}
+
+/// Asynchronous counterpart of `Interpreter`, which instead of blocking
+/// waits for the input and output and yields every `yield_every` steps,
+/// so that many programs can be run on a single thread.
+pub struct AsyncInterpreter<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> {
+    /// does all the actual work, its input holds the bytes that were already read
+    /// but not yet consumed and its output what wasn't yet written
+    inner: Interpreter<PendingInput, Vec<u8>>,
+    yield_every: usize,
+    pub input: R,
+    pub output: W,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> AsyncInterpreter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self::with_options(input, output, Options::default())
+    }
+
+    pub fn with_options(input: R, output: W, options: Options) -> Self {
+        Self {
+            inner: Interpreter::with_options(PendingInput::default(), Vec::new(), options),
+            yield_every: DEFAULT_YIELD_EVERY,
+            input,
+            output,
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.set_limits(limits);
+    }
+
+    /// Sets how many instructions are executed before yielding, must be at least 1.
+    pub fn set_yield_every(&mut self, steps: usize) {
+        self.yield_every = steps.max(1);
+    }
+
+    pub async fn execute(&mut self, program: &mut Program) -> Result<(), RuntimeError> {
+        loop {
+            let status = self.inner.step(program, self.yield_every);
+            self.output
+                .write_all(&self.inner.output)
+                .await
+                .map_err(|e| RuntimeError::Output(e.to_string(), program.current_position()))?;
+            self.inner.output.clear();
+
+            match status {
+                Status::Finished => break,
+                Status::Error(e) => return Err(e),
+                Status::Paused(_) => tokio::task::yield_now().await,
+                Status::NeedsInput => {
+                    // make sure that everything was printed before waiting for the input,
+                    // e.g. a prompt
+                    self.output.flush().await.map_err(|e| {
+                        RuntimeError::Output(e.to_string(), program.current_position())
+                    })?;
+                    let mut buf = [0; 1024];
+                    match self.input.read(&mut buf).await {
+                        Ok(0) => self.inner.input.end_of_input = true,
+                        Ok(n) => self.inner.input.buffer.extend(&buf[..n]),
+                        Err(e) => {
+                            return Err(RuntimeError::Input(
+                                e.to_string(),
+                                program.current_position(),
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        self.output
+            .flush()
+            .await
+            .map_err(|e| RuntimeError::Output(e.to_string(), program.current_position()))
+    }
+}
+
+#[cfg(test)]
+mod async_interpreter_tests {
+    use tokio::io::duplex;
+
+    use super::*;
+    use crate::parser::Parser;
+
+    #[tokio::test]
+    async fn test_echo_over_duplex() {
+        let (mut client, server) = duplex(64);
+        let (server_read, server_write) = tokio::io::split(server);
+        let mut interpreter = AsyncInterpreter::new(server_read, server_write);
+        let mut program = Parser::parse(",[.,]").unwrap();
+
+        let session = tokio::spawn(async move {
+            let result = interpreter.execute(&mut program).await;
+            (result, interpreter)
+        });
+
+        client.write_all(b"hi").await.unwrap();
+        let mut buf = [0; 2];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hi");
+        client.write_all(&[0]).await.unwrap();
+
+        let (result, _) = session.await.unwrap();
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_end_of_input() {
+        let mut interpreter = AsyncInterpreter::new(&b""[..], Vec::new());
+        let mut program = Parser::parse("+.,").unwrap();
+
+        assert!(matches!(
+            interpreter.execute(&mut program).await,
+            Err(RuntimeError::Input(_, _))
+        ));
+        assert_eq!(interpreter.output, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_eof_policy() {
+        let mut interpreter = AsyncInterpreter::with_options(
+            &b"a"[..],
+            Vec::new(),
+            Options {
+                eof_policy: crate::interpreter::options::EofPolicy::Zero,
+                ..Options::default()
+            },
+        );
+        let mut program = Parser::parse(",[.,]+.").unwrap();
+
+        assert_eq!(interpreter.execute(&mut program).await, Ok(()));
+        assert_eq!(interpreter.output, b"a\x01");
+    }
+
+    #[tokio::test]
+    async fn test_yielding_keeps_state() {
+        let mut interpreter = AsyncInterpreter::new(&b""[..], Vec::new());
+        interpreter.set_yield_every(1);
+        let mut program = Parser::parse("++++++++[>++++++++<-]>+.").unwrap();
+
+        assert_eq!(interpreter.execute(&mut program).await, Ok(()));
+        assert_eq!(interpreter.output, b"A");
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let mut interpreter = AsyncInterpreter::new(&b""[..], Vec::new());
+        interpreter.set_limits(Limits {
+            max_steps: Some(10),
+            ..Limits::default()
+        });
+        let mut program = Parser::parse("+[]").unwrap();
+
+        assert!(matches!(
+            interpreter.execute(&mut program).await,
+            Err(RuntimeError::StepLimitExceeded(_))
+        ));
+    }
+}
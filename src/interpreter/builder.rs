@@ -0,0 +1,113 @@
+use std::io::{Read, Write};
+
+use super::{
+    limits::Limits,
+    options::{EofPolicy, FlushPolicy, Options, OutputMode},
+    Interpreter,
+};
+use crate::tape::{CellType, TapeMode};
+
+/// Creates an `Interpreter` which owns its input and output,
+/// those can be anything that implements `Read` and `Write`,
+/// including `&mut` references or boxed trait objects.
+///
+/// ```
+/// use biir::interpreter::{options::EofPolicy, InterpreterBuilder};
+/// use biir::tape::CellType;
+///
+/// let input: Box<dyn std::io::Read + Send> = Box::new(&b"a"[..]);
+/// let mut interpreter = InterpreterBuilder::new(input, Vec::new())
+///     .cell_type(CellType::U8)
+///     .eof_policy(EofPolicy::Zero)
+///     .build();
+/// ```
+pub struct InterpreterBuilder<R: Read, W: Write> {
+    input: R,
+    output: W,
+    options: Options,
+}
+
+impl<R: Read, W: Write> InterpreterBuilder<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            options: Options::default(),
+        }
+    }
+
+    pub fn cell_type(mut self, cell_type: CellType) -> Self {
+        self.options.cell_type = cell_type;
+        self
+    }
+
+    pub fn eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.options.eof_policy = eof_policy;
+        self
+    }
+
+    /// NOTE: wrapping tape with no cells will have one
+    pub fn tape_mode(mut self, tape_mode: TapeMode) -> Self {
+        self.options.tape_mode = tape_mode;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.options.limits = limits;
+        self
+    }
+
+    pub fn output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.options.output_mode = output_mode;
+        self
+    }
+
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.options.flush_policy = flush_policy;
+        self
+    }
+
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> Interpreter<R, W> {
+        Interpreter::with_options(self.input, self.output, self.options)
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_owned_streams() {
+        let input: Box<dyn Read + Send> = Box::new(&b"A"[..]);
+        let output: Box<dyn Write + Send> = Box::new(Vec::new());
+        let mut interpreter = InterpreterBuilder::new(input, output).build();
+        assert_send(&interpreter);
+
+        let mut program = Parser::parse(",.").unwrap();
+        let handle = std::thread::spawn(move || interpreter.execute(&mut program));
+        assert_eq!(handle.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_options() {
+        let mut interpreter = InterpreterBuilder::new(&b""[..], Vec::new())
+            .cell_type(CellType::U8)
+            .eof_policy(EofPolicy::Max)
+            .tape_mode(TapeMode::Wrapping(2))
+            .output_mode(OutputMode::Numerical)
+            .build();
+        let mut program = Parser::parse("<,.>+++++[>+++++++<-]>-.++.").unwrap();
+
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        let (_, output) = interpreter.into_inner();
+        assert_eq!(output, b"255\n33\n35\n");
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use crate::parser::{instruction::Operation, Program};
+
+/// How many times each instruction of a program was executed,
+/// possibly summed over many executions with `merge`.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Coverage {
+    /// indexed by the address of the instruction
+    hits: Vec<usize>,
+}
+
+impl Coverage {
+    pub fn hit(&mut self, addr: usize) {
+        if addr >= self.hits.len() {
+            self.hits.resize(addr + 1, 0);
+        }
+        self.hits[addr] += 1;
+    }
+
+    /// Number of times the instruction at `addr` was executed.
+    pub fn hits(&self, addr: usize) -> usize {
+        self.hits.get(addr).copied().unwrap_or(0)
+    }
+
+    /// Adds the counts from another execution of the same program.
+    pub fn merge(&mut self, other: &Coverage) {
+        if other.hits.len() > self.hits.len() {
+            self.hits.resize(other.hits.len(), 0);
+        }
+        for (hits, n) in self.hits.iter_mut().zip(&other.hits) {
+            *hits += n;
+        }
+    }
+
+    /// Hits of every line (counting from 0) that has any instructions,
+    /// which is the highest count of the instructions on it.
+    fn lines(&self, program: &Program) -> BTreeMap<usize, usize> {
+        let mut lines = BTreeMap::new();
+        for (addr, instruction) in program.instructions().iter().enumerate() {
+            let hits = lines
+                .entry(instruction.get_position().line_number)
+                .or_insert(0);
+            *hits = self.hits(addr).max(*hits);
+        }
+        lines
+    }
+
+    /// Source of the program with each line prefixed with the number of times it was executed,
+    /// in the same way as gcov does: `-` for lines without instructions and `#####`
+    /// for ones that never ran. Lines which ran only partially are followed by
+    /// another one pointing at the instructions that didn't.
+    pub fn annotate(&self, program: &Program, src: &str) -> String {
+        let lines = self.lines(program);
+        let mut missed: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (addr, instruction) in program.instructions().iter().enumerate() {
+            let pos = instruction.get_position();
+            if self.hits(addr) == 0 && lines.get(&pos.line_number) != Some(&0) {
+                missed
+                    .entry(pos.line_number)
+                    .or_default()
+                    .push(pos.char_number);
+            }
+        }
+
+        let mut annotated = String::new();
+        for (line_number, line) in src.lines().enumerate() {
+            let count = match lines.get(&line_number) {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(n) => n.to_string(),
+            };
+            annotated.push_str(&format!("{:>9}:{:>5}:{}\n", count, line_number + 1, line));
+
+            if let Some(chars) = missed.get(&line_number) {
+                // keep the tabs so that the markers line up with the source
+                let mut markers = line
+                    .chars()
+                    .take(chars.iter().max().unwrap() + 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<Vec<_>>();
+                for &c in chars {
+                    if let Some(marker) = markers.get_mut(c) {
+                        *marker = '^';
+                    }
+                }
+                let markers = markers.into_iter().collect::<String>();
+                annotated.push_str(&format!("{:>9} {:>5} {}\n", "", "", markers));
+            }
+        }
+        annotated
+    }
+
+    /// Report in the LCOV tracefile format, where lines are counted from 1
+    /// and the body of each loop is a branch which was taken as many times
+    /// as the loop has iterated.
+    pub fn lcov(&self, program: &Program, source_file: &str) -> String {
+        let mut report = format!("TN:\nSF:{}\n", source_file);
+
+        let mut branches = 0;
+        let mut branches_hit = 0;
+        for (addr, instruction) in program.instructions().iter().enumerate() {
+            if let Operation::BeginLoop(_) = instruction.get_op() {
+                // the instruction right after `[` runs once for every iteration,
+                // even if it's the `]` of an empty loop
+                let taken = match (self.hits(addr), self.hits(addr + 1)) {
+                    (0, _) => "-".to_string(),
+                    (_, n) => n.to_string(),
+                };
+                if self.hits(addr + 1) > 0 {
+                    branches_hit += 1;
+                }
+                report.push_str(&format!(
+                    "BRDA:{},{},0,{}\n",
+                    instruction.get_position().line_number + 1,
+                    branches,
+                    taken
+                ));
+                branches += 1;
+            }
+        }
+        if branches > 0 {
+            report.push_str(&format!("BRF:{}\nBRH:{}\n", branches, branches_hit));
+        }
+
+        let lines = self.lines(program);
+        for (line_number, hits) in &lines {
+            report.push_str(&format!("DA:{},{}\n", line_number + 1, hits));
+        }
+        report.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            lines.len(),
+            lines.values().filter(|&&hits| hits > 0).count()
+        ));
+        report
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+
+    fn run(src: &str, input: &[u8]) -> (Program, Coverage) {
+        let mut program = Parser::parse(src).unwrap();
+        let mut interpreter = Interpreter::new(Cursor::new(input.to_vec()), Vec::new());
+        interpreter.record_coverage();
+        interpreter.execute(&mut program).unwrap();
+        (program, interpreter.take_coverage().unwrap())
+    }
+
+    #[test]
+    fn test_hits() {
+        let (_, coverage) = run("+++[-]>[.]", b"");
+        // `+++` is a single instruction
+        assert_eq!(
+            (0..7).map(|addr| coverage.hits(addr)).collect::<Vec<_>>(),
+            vec![1, 1, 3, 3, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let src = "set to one\n+\n,[\n.[-]\n]\n";
+        let (program, coverage) = run(src, b"\x00");
+        assert_eq!(
+            coverage.annotate(&program, src),
+            "        -:    1:set to one\n\
+             \x20       1:    2:+\n\
+             \x20       1:    3:,[\n\
+             \x20   #####:    4:.[-]\n\
+             \x20   #####:    5:]\n"
+        );
+
+        let src = "+[-]>[.]";
+        let (program, coverage) = run(src, b"");
+        assert_eq!(
+            coverage.annotate(&program, src),
+            format!("        1:    1:+[-]>[.]\n{}^^\n", " ".repeat(22))
+        );
+    }
+
+    #[test]
+    fn test_lcov_and_merge() {
+        let src = ",[\n.,]";
+        let (program, mut coverage) = run(src, b"\x00");
+        coverage.merge(&run(src, b"ab\x00").1);
+        assert_eq!(
+            coverage.lcov(&program, "echo.bf"),
+            "TN:\nSF:echo.bf\n\
+             BRDA:1,0,0,2\nBRF:1\nBRH:1\n\
+             DA:1,2\nDA:2,2\n\
+             LF:2\nLH:2\nend_of_record\n"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use core::fmt::Display;
+
+use crate::parser::utils::Position;
+
+/// Errors that can stop the execution of a program.
+/// Each of them carries the position of the instruction that was being executed.
+#[derive(Debug, PartialEq)]
+pub enum RuntimeError {
+    StepLimitExceeded(Position),
+    TimeLimitExceeded(Position),
+    TapeLimitExceeded(Position),
+    OutputLimitExceeded(Position),
+    /// Tape refused to perform the operation, e.g. moving to the negative side.
+    Tape(String, Position),
+    /// Called procedure wasn't defined.
+    Procedure(String, Position),
+    /// Reading from the input failed.
+    Input(String, Position),
+    /// Writing to the output failed.
+    Output(String, Position),
+}
+
+impl RuntimeError {
+    pub fn position(&self) -> &Position {
+        match self {
+            RuntimeError::StepLimitExceeded(pos)
+            | RuntimeError::TimeLimitExceeded(pos)
+            | RuntimeError::TapeLimitExceeded(pos)
+            | RuntimeError::OutputLimitExceeded(pos)
+            | RuntimeError::Tape(_, pos)
+            | RuntimeError::Procedure(_, pos)
+            | RuntimeError::Input(_, pos)
+            | RuntimeError::Output(_, pos) => pos,
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            RuntimeError::StepLimitExceeded(_) => "Step limit exceeded",
+            RuntimeError::TimeLimitExceeded(_) => "Time limit exceeded",
+            RuntimeError::TapeLimitExceeded(_) => "Tape length limit exceeded",
+            RuntimeError::OutputLimitExceeded(_) => "Output limit exceeded",
+            RuntimeError::Tape(msg, _)
+            | RuntimeError::Procedure(msg, _)
+            | RuntimeError::Input(msg, _)
+            | RuntimeError::Output(msg, _) => msg,
+        };
+        f.write_fmt(format_args!("{} at {}", msg, self.position()))
+    }
+}
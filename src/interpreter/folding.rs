@@ -0,0 +1,224 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    time::{Duration, Instant},
+};
+
+use super::{
+    error::RuntimeError, options::Options, snapshot::Snapshot, status::Status, Interpreter,
+};
+use crate::parser::{instruction::Operation, Program};
+
+/// Input which never has anything to read, so that the program stops at its first `,`.
+struct NoInput;
+
+impl Read for NoInput {
+    fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+        Err(ErrorKind::WouldBlock.into())
+    }
+}
+
+/// Result of executing the part of a program which doesn't depend on its input,
+/// e.g. `++++++++[>++++++++<-]` computing a constant before the first `,`.
+/// Instead of doing it again on every run, the tape can be initialized straight
+/// from the snapshot and the output it produced printed as it is.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Prefix {
+    /// state right before the first instruction that needs the input,
+    /// or after the last one if the program doesn't read anything
+    pub snapshot: Snapshot,
+    /// everything that was printed until then
+    pub output: Vec<u8>,
+    /// how long it took, which counts towards the timeout like its steps
+    pub elapsed: Duration,
+}
+
+impl Prefix {
+    /// Executes at most `max_steps` instructions of the program with the given options,
+    /// stopping once it needs the input. The program is reset afterwards
+    /// and the prefix stored with it is dropped, so that it's evaluated from scratch.
+    /// Returns `None` if the prefix can't be folded: when it fails, since the error
+    /// should happen when it's actually run, or when the program uses brainfork threads
+    /// or Boolfuck's bit IO, whose state isn't kept in snapshots.
+    pub fn evaluate(program: &mut Program, options: Options, max_steps: usize) -> Option<Self> {
+        let unsupported = program.instructions().iter().any(|i| {
+            matches!(
+                i.get_op(),
+                Operation::Fork | Operation::BitRead | Operation::BitWrite
+            )
+        });
+        program.set_prefix(None);
+        if unsupported {
+            return None;
+        }
+
+        let started = Instant::now();
+        let mut interpreter = Interpreter::with_options(NoInput, Vec::new(), options);
+        let status = interpreter.step(program, max_steps);
+        let prefix = match status {
+            Status::Error(_) => None,
+            _ => Some(interpreter.snapshot(program)),
+        }
+        .map(|mut snapshot| {
+            // counters are cleared once the program finishes
+            snapshot.output_position = interpreter.output.len();
+            Prefix {
+                snapshot,
+                output: interpreter.output,
+                elapsed: started.elapsed(),
+            }
+        });
+        program.reset();
+        prefix
+    }
+}
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+    /// Puts the interpreter and the program in the state after the prefix
+    /// and prints its output, so that only the rest of the program has to be executed.
+    /// Its steps, output and time count towards the limits as if it was executed.
+    pub fn apply_prefix(&mut self, program: &mut Program, prefix: &Prefix) -> Result<(), String> {
+        self.restore(program, &prefix.snapshot)?;
+        self.print_prefix(program, prefix)
+            .map_err(|e| e.to_string())
+    }
+
+    fn print_prefix(&mut self, program: &Program, prefix: &Prefix) -> Result<(), RuntimeError> {
+        let now = Instant::now();
+        self.started = Some(now.checked_sub(prefix.elapsed).unwrap_or(now));
+        let pos = program.current_position();
+        if let Some(max_output_bytes) = self.options.limits.max_output_bytes {
+            if self.output_bytes > max_output_bytes {
+                return Err(RuntimeError::OutputLimitExceeded(pos));
+            }
+        }
+        self.write_output(&prefix.output, pos)?;
+        self.flush_output(pos)
+    }
+
+    /// Applies the prefix stored with the program if it's just starting on a blank tape,
+    /// which is what the prefix was evaluated on.
+    pub(super) fn apply_stored_prefix(
+        &mut self,
+        program: &mut Program,
+    ) -> Result<(), RuntimeError> {
+        let starting = self.started.is_none()
+            && self.steps == 0
+            && program.get_pc() == 0
+            && program.get_stack().is_empty()
+            && self.threads.is_empty();
+        let blank = self.tape.is_blank() && self.storage.0 == 0 && self.output_bits.1 == 0;
+        if !starting || !blank || self.coverage.is_some() {
+            return Ok(());
+        }
+        let prefix = match program.prefix().cloned() {
+            Some(prefix) => prefix,
+            None => return Ok(()),
+        };
+        // it was evaluated on the same program, so only the tape limit can stop it
+        let pos = program.current_position();
+        self.restore(program, &prefix.snapshot)
+            .map_err(|e| RuntimeError::Tape(e, pos))?;
+        self.print_prefix(program, &prefix)
+    }
+}
+
+#[cfg(test)]
+mod folding_tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::parser::{dialect::Dialect, Parser};
+
+    #[test]
+    fn test_prefix_before_input() {
+        let mut program = Parser::parse("++++++++[>++++++++<-]>+.,.").unwrap();
+        let prefix = Prefix::evaluate(&mut program, Options::default(), 1000).unwrap();
+        assert_eq!(prefix.output, b"A");
+        assert_eq!(prefix.snapshot.tape, vec![0, 65]);
+        assert_eq!(program.get_pc(), 0);
+
+        let mut interpreter = Interpreter::new(Cursor::new(b"x".to_vec()), Vec::new());
+        interpreter.apply_prefix(&mut program, &prefix).unwrap();
+        interpreter.execute(&mut program).unwrap();
+        assert_eq!(interpreter.output, b"Ax");
+    }
+
+    #[test]
+    fn test_whole_program() {
+        let mut program = Parser::parse("++++++++[>++++++++<-]>+.+.").unwrap();
+        let prefix = Prefix::evaluate(&mut program, Options::default(), 1000).unwrap();
+        assert_eq!(prefix.output, b"AB");
+        assert_eq!(prefix.snapshot.pc, program.instructions().len());
+
+        let mut interpreter = Interpreter::new(Cursor::new(Vec::new()), Vec::new());
+        interpreter.apply_prefix(&mut program, &prefix).unwrap();
+        interpreter.execute(&mut program).unwrap();
+        assert_eq!(interpreter.output, b"AB");
+    }
+
+    #[test]
+    fn test_step_budget_and_errors() {
+        let mut program = Parser::parse("+[]").unwrap();
+        let prefix = Prefix::evaluate(&mut program, Options::default(), 10).unwrap();
+        assert_eq!(prefix.snapshot.steps, 10);
+
+        let mut program = Parser::parse("<,").unwrap();
+        assert_eq!(Prefix::evaluate(&mut program, Options::default(), 10), None);
+
+        let mut program = Dialect::Brainfork.parse("+Y.").unwrap();
+        assert_eq!(Prefix::evaluate(&mut program, Options::default(), 10), None);
+    }
+
+    #[test]
+    fn test_fold_prefix_option() {
+        let options = Options {
+            fold_prefix: Some(1000),
+            ..Options::default()
+        };
+        let src = "++++++++[>++++++++<-]>+.,.";
+        let mut interpreter =
+            Interpreter::with_options(Cursor::new(b"x".to_vec()), Vec::new(), options);
+        assert_eq!(interpreter.run_source(src), Ok(()));
+        assert_eq!(interpreter.output, b"Ax");
+
+        // steps of the prefix still count towards the limit
+        let mut options = options;
+        options.limits.max_steps = Some(5);
+        let mut interpreter =
+            Interpreter::with_options(Cursor::new(b"x".to_vec()), Vec::new(), options);
+        assert!(interpreter.run_source(src).is_err());
+    }
+
+    #[test]
+    fn test_stored_prefix() {
+        let mut program = Parser::parse("++++++++[>++++++++<-]>+.,.").unwrap();
+        let mut prefix = Prefix::evaluate(&mut program, Options::default(), 1000).unwrap();
+        program.set_prefix(Some(prefix.clone()));
+        let mut interpreter = Interpreter::new(Cursor::new(b"xy".to_vec()), Vec::new());
+        interpreter.execute(&mut program).unwrap();
+        assert_eq!(interpreter.output, b"Ax");
+
+        // the tape isn't blank anymore, so the prefix doesn't apply
+        program.reset();
+        interpreter.execute(&mut program).unwrap();
+        let mut unfolded = Parser::parse("++++++++[>++++++++<-]>+.,.").unwrap();
+        let mut expected = Interpreter::new(Cursor::new(b"xy".to_vec()), Vec::new());
+        expected.execute(&mut unfolded).unwrap();
+        unfolded.reset();
+        expected.execute(&mut unfolded).unwrap();
+        assert_eq!(interpreter.output, expected.output);
+
+        // the time it took counts towards the timeout
+        prefix.elapsed = std::time::Duration::from_secs(10);
+        program.reset();
+        program.set_prefix(Some(prefix));
+        let mut options = Options::default();
+        options.limits.timeout = Some(std::time::Duration::from_secs(1));
+        let mut interpreter =
+            Interpreter::with_options(Cursor::new(b"x".to_vec()), Vec::new(), options);
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::TimeLimitExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+/// Upper bounds on the resources a program is allowed to use.
+/// Every limit is optional and `None` means that it won't be checked at all,
+/// which is also the default.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// maximum number of executed instructions,
+    /// NOTE: grouped operations (like `+++`) count as a single step
+    pub max_steps: Option<usize>,
+    /// maximum wall-clock time of a single execution
+    pub timeout: Option<Duration>,
+    /// maximum number of cells the tape pointer can reach, on a wrapping tape
+    /// it's checked wherever the pointer lands, so `<` can exceed it as well
+    pub max_tape_length: Option<usize>,
+    /// maximum number of bytes that can be written to the output
+    pub max_output_bytes: Option<usize>,
+}
@@ -1,47 +1,321 @@
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod builder;
+pub mod coverage;
+pub mod error;
+pub mod folding;
+pub mod limits;
+pub mod options;
+pub mod snapshot;
+pub mod status;
+
 use colored::*;
-use std::io::Write;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Write};
+use std::iter;
 use std::num::Wrapping;
+use std::time::Instant;
 use std::{fs, io::Read};
 
 use crate::{
-    parser::{instruction::Operation, Parser, Program},
-    tape::Tape,
+    parser::{
+        dialect::Dialect,
+        instruction::{Instruction, Operation},
+        utils::Position,
+        Context, Program,
+    },
+    tape::{CellType, Tape, TapeMode},
 };
+pub use builder::InterpreterBuilder;
+use coverage::Coverage;
+use error::RuntimeError;
+use folding::Prefix;
+use limits::Limits;
+use options::{EofPolicy, FlushPolicy, Options, OutputMode};
+use snapshot::Snapshot;
+use status::Status;
+
+/// Regardless of the flush policy output is written out once this many bytes are waiting.
+const MAX_PENDING_OUTPUT: usize = 8 * 1024;
 
-pub struct Interpreter<'a, R: Read, W: Write> {
+/// Runs programs of all the supported dialects.
+///
+/// Brainfork's `Y` forks the current thread: in the parent the current cell is set to 0,
+/// while the child starts right after `Y` with its pointer moved one cell to the right,
+/// where the cell is set to 1. All the threads share the tape and take turns
+/// executing one instruction each, so their output is always the same.
+pub struct Interpreter<R: Read, W: Write> {
     tape: Tape,
-    // for some reason it's neccessary to have this as mutable because .read() takes that?
-    pub input: &'a mut R,
-    pub output: &'a mut W,
+    /// cells and tape required by the program that was run last, which `tape` was made for,
+    /// while the tape made with the options is put aside in `options_tape`
+    tape_required: (Option<CellType>, Option<TapeMode>),
+    options_tape: Option<Tape>,
+    /// storage cell of Extended Type I
+    storage: Wrapping<usize>,
+    /// bits of the last read byte which weren't yet read by Boolfuck's `,`,
+    /// and how many of them are left
+    input_bits: (u8, u8),
+    /// bits printed by Boolfuck's `;` which don't make a whole byte yet,
+    /// and how many of them there are
+    output_bits: (u8, u8),
+    /// brainfork threads waiting for their turn, with the positions of their pointers
+    threads: VecDeque<(Context, usize)>,
+    options: Options,
+    /// when the currently running program executed its first instruction
+    started: Option<Instant>,
+    /// number of instructions executed so far
+    steps: usize,
+    /// number of bytes read from the input so far
+    input_bytes: usize,
+    /// number of bytes written to the output so far
+    output_bytes: usize,
+    /// output that wasn't yet written out because of the flush policy,
+    /// it's always written out before returning from `resume`,
+    /// so it doesn't have to be flushed on drop nor kept in snapshots
+    pending_output: Vec<u8>,
+    /// counts of executed instructions, only kept once `record_coverage` was called
+    coverage: Option<Coverage>,
+    pub input: R,
+    pub output: W,
 }
 
-impl<'a, R: Read, W: Write> Interpreter<'a, R, W> {
-    pub fn new(input: &'a mut R, output: &'a mut W) -> Self {
+impl<R: Read, W: Write> Interpreter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self::with_options(input, output, Options::default())
+    }
+
+    pub fn with_options(input: R, output: W, options: Options) -> Self {
         Self {
-            tape: Tape::default(),
+            tape: Tape::new(options.cell_type, options.tape_mode),
+            tape_required: (None, None),
+            options_tape: None,
+            storage: Wrapping(0),
+            input_bits: (0, 0),
+            output_bits: (0, 0),
+            threads: VecDeque::new(),
+            options,
+            started: None,
+            steps: 0,
+            input_bytes: 0,
+            output_bytes: 0,
+            pending_output: Vec::new(),
+            coverage: None,
             input,
             output,
         }
     }
 
-    fn handle_dot(&mut self) {
-        let numerical_mode = false;
-        if numerical_mode {
-            let _ = self
-                .output
-                .write(format!("{}\n", self.tape.current_value).as_bytes());
+    pub fn builder(input: R, output: W) -> InterpreterBuilder<R, W> {
+        InterpreterBuilder::new(input, output)
+    }
+
+    /// Gives back the input and the output, e.g. to inspect what was written to a `Vec`.
+    pub fn into_inner(self) -> (R, W) {
+        (self.input, self.output)
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.options.limits = limits;
+    }
+
+    /// Starts counting how many times each instruction was executed,
+    /// across all the programs run until the counts are taken with `take_coverage`.
+    pub fn record_coverage(&mut self) {
+        self.coverage = Some(Coverage::default());
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    fn handle_dot(&mut self, pos: Position) -> Result<(), RuntimeError> {
+        let value = self.tape.current_value.0;
+        let bytes = match self.options.output_mode {
+            OutputMode::Char => match char::from_u32(value as u32) {
+                Some(c) if value <= u32::MAX as usize => c.to_string().into_bytes(),
+                _ => {
+                    return Err(RuntimeError::Output(
+                        format!("{} is not a valid unicode character", value),
+                        pos,
+                    ))
+                }
+            },
+            OutputMode::Byte => vec![value as u8],
+            OutputMode::Numerical => format!("{}\n", value).into_bytes(),
+        };
+
+        self.print(&bytes, pos)
+    }
+
+    /// Writes out the output of the program, counting it towards the limit.
+    fn print(&mut self, bytes: &[u8], pos: Position) -> Result<(), RuntimeError> {
+        self.output_bytes += bytes.len();
+        if let Some(max_output_bytes) = self.options.limits.max_output_bytes {
+            if self.output_bytes > max_output_bytes {
+                return Err(RuntimeError::OutputLimitExceeded(pos));
+            }
+        }
+        self.write_output(bytes, pos)
+    }
+
+    fn handle_bit_read(&mut self, pos: Position) -> Result<(), RuntimeError> {
+        let (byte, count) = self.output_bits;
+        let byte = byte | (self.tape.current_value.0 as u8 & 1) << count;
+        if count == 7 {
+            self.output_bits = (0, 0);
+            self.print(&[byte], pos)
         } else {
-            let _ = self.output.write(
-                format!(
-                    "{}",
-                    char::from_u32(self.tape.current_value.0 as u32)
-                        .expect("big-int mode was used without numerical mode!")
-                )
-                .as_bytes(),
-            );
+            self.output_bits = (byte, count + 1);
+            Ok(())
         }
     }
 
+    fn handle_bit_write(&mut self, pos: Position) -> Result<(), Status> {
+        if self.input_bits.1 == 0 {
+            match self.read_byte(pos)? {
+                Some(byte) => self.input_bits = (byte, 8),
+                // the cell was already set according to the EOF policy
+                None => return Ok(()),
+            }
+        }
+        let (byte, count) = self.input_bits;
+        self.tape.set_current_value(Wrapping((byte & 1).into()));
+        self.input_bits = (byte >> 1, count - 1);
+        Ok(())
+    }
+
+    /// Reads the next byte of the input, when there is nothing more to read
+    /// the current cell is set according to the EOF policy and None is returned.
+    fn read_byte(&mut self, pos: Position) -> Result<Option<u8>, Status> {
+        // whatever was printed so far may be a prompt for this input
+        self.flush_output(pos).map_err(Status::Error)?;
+        let mut buf: [u8; 1] = [0];
+        // not read_exact, which would keep retrying after the input was interrupted
+        match self.input.read(&mut buf[..]) {
+            Ok(0) => {
+                let e = std::io::Error::from(ErrorKind::UnexpectedEof);
+                self.handle_eof(pos, e).map_err(Status::Error)?;
+                Ok(None)
+            }
+            Ok(_) => {
+                self.input_bytes += 1;
+                Ok(Some(buf[0]))
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Err(Status::NeedsInput),
+            Err(e) => Err(Status::Error(RuntimeError::Input(e.to_string(), pos))),
+        }
+    }
+
+    fn write_output(&mut self, bytes: &[u8], pos: Position) -> Result<(), RuntimeError> {
+        self.pending_output.extend_from_slice(bytes);
+        let flush = self.pending_output.len() >= MAX_PENDING_OUTPUT
+            || match self.options.flush_policy {
+                FlushPolicy::Byte => true,
+                FlushPolicy::Newline => bytes.contains(&b'\n'),
+                FlushPolicy::OnInput => false,
+            };
+        if flush {
+            self.flush_output(pos)?;
+        }
+        Ok(())
+    }
+
+    fn flush_output(&mut self, pos: Position) -> Result<(), RuntimeError> {
+        if self.pending_output.is_empty() {
+            return Ok(());
+        }
+        self.output
+            .write_all(&self.pending_output)
+            .and_then(|_| self.output.flush())
+            .map_err(|e| RuntimeError::Output(e.to_string(), pos))?;
+        self.pending_output.clear();
+        Ok(())
+    }
+
+    /// Sets the current cell after `,` found nothing to read.
+    fn handle_eof(&mut self, pos: Position, e: std::io::Error) -> Result<(), RuntimeError> {
+        match self.options.eof_policy {
+            EofPolicy::Error => return Err(RuntimeError::Input(e.to_string(), pos)),
+            EofPolicy::Zero => self.tape.set_current_value(Wrapping(0)),
+            EofPolicy::Max => self
+                .tape
+                .set_current_value(Wrapping(self.tape.get_cell_type().max_value())),
+            EofPolicy::Unchanged => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the pointer by `n` cells unless it would go beyond the tape length limit.
+    fn move_pointer(&mut self, n: usize, right: bool, pos: Position) -> Result<(), RuntimeError> {
+        let destination = self.tape.destination(n, right);
+        let growth = if right { 0 } else { self.tape.left_growth(n) };
+        if let Some(max_tape_length) = self.options.limits.max_tape_length {
+            let length = self.tape.cells().len().max(self.tape.current_position + 1);
+            // going right past the end of the address space is going past the limit as well
+            if matches!(destination, Ok(d) if d >= max_tape_length)
+                || (right && destination.is_err())
+                || (growth > 0 && length + growth > max_tape_length)
+            {
+                return Err(RuntimeError::TapeLimitExceeded(pos));
+            }
+        }
+        let moved = if right {
+            self.tape.move_right(n)
+        } else {
+            self.tape.move_left(n)
+        };
+        moved.map_err(|e| RuntimeError::Tape(e, pos))?;
+        // cells added on the left move all the others, including the ones other threads are at
+        for (_, position) in self.threads.iter_mut() {
+            *position += growth;
+        }
+        Ok(())
+    }
+
+    /// Switches to the cells and the tape the program's dialect has to be run on,
+    /// starting with an empty one if they are different from the current ones,
+    /// or back to the tape from the options if the program doesn't require anything.
+    fn use_program_tape(&mut self, program: &Program) {
+        let required = (program.cell_type(), program.tape_mode());
+        if required == self.tape_required {
+            return;
+        }
+        match required {
+            (None, None) => {
+                self.tape = self
+                    .options_tape
+                    .take()
+                    .expect("tape from the options is put aside while it's not used");
+            }
+            (cell_type, tape_mode) => {
+                let tape = Tape::new(
+                    cell_type.unwrap_or(self.options.cell_type),
+                    tape_mode.unwrap_or(self.options.tape_mode),
+                );
+                let tape = std::mem::replace(&mut self.tape, tape);
+                if self.options_tape.is_none() {
+                    self.options_tape = Some(tape);
+                }
+            }
+        }
+        self.tape_required = required;
+    }
+
+    /// Makes sure that executing the next instruction won't exceed any of the limits.
+    fn check_limits(&self, pos: Position) -> Result<(), RuntimeError> {
+        if let Some(max_steps) = self.options.limits.max_steps {
+            if self.steps >= max_steps {
+                return Err(RuntimeError::StepLimitExceeded(pos));
+            }
+        }
+        if let (Some(timeout), Some(started)) = (self.options.limits.timeout, self.started) {
+            if started.elapsed() >= timeout {
+                return Err(RuntimeError::TimeLimitExceeded(pos));
+            }
+        }
+        Ok(())
+    }
+
     pub fn run<P: AsRef<std::path::Path>>(&mut self, file: P) -> Result<(), String> {
         let file_path = file.as_ref();
 
@@ -53,43 +327,320 @@ impl<'a, R: Read, W: Write> Interpreter<'a, R, W> {
             )
         })?;
 
-        let mut program = Parser::parse(&src).map_err(|e| {
-            format!(
-                "Error occured while parsing {}: {}",
-                file_path.display().to_string().bold(),
-                e
-            )
-        })?;
-        self.execute(&mut program)
-    }
-
-    pub fn execute(&mut self, program: &mut Program) -> Result<(), String> {
-        while let Some(instruction) = program.fetch_instruction() {
-            match *instruction.get_op() {
-                Operation::TapeLeft => self.tape.move_left(instruction.get_n())?,
-                Operation::TapeRight => self.tape.move_right(instruction.get_n())?,
-                Operation::TapePrint => {
-                    // this is just debug information, so even if this fails it's not fatal
-                    // and it's probably ok to just ignore it
-                    let _ = self
-                        .output
-                        .write(format!("!TAPE: {}", self.tape).as_bytes());
+        let dialect = Dialect::from_path(file_path).unwrap_or(Dialect::Brainfuck);
+        self.run_dialect_source(&src, &file_path.display().to_string(), &dialect)
+    }
+
+    /// Parses and executes the program in src,
+    /// errors will point at the place in src where they happened.
+    pub fn run_source(&mut self, src: &str) -> Result<(), String> {
+        self.run_source_named(src, "source")
+    }
+
+    /// Same as `run_source` but parsing errors will mention where src came from.
+    pub fn run_source_named(&mut self, src: &str, name: &str) -> Result<(), String> {
+        self.run_dialect_source(src, name, &Dialect::Brainfuck)
+    }
+
+    /// Same as `run_source_named` but src is written in the given dialect.
+    pub fn run_dialect_source(
+        &mut self,
+        src: &str,
+        name: &str,
+        dialect: &Dialect,
+    ) -> Result<(), String> {
+        let mut program = dialect
+            .parse(src)
+            .map_err(|e| format!("Error occured while parsing {}: {}", name.bold(), e))?;
+        if let (Some(max_steps), None) = (self.options.fold_prefix, &self.coverage) {
+            let prefix = Prefix::evaluate(&mut program, self.options, max_steps);
+            program.set_prefix(prefix);
+        }
+        self.execute(&mut program).map_err(|e| e.to_string())
+    }
+
+    /// Runs the whole program, blocking on input if necessary.
+    pub fn execute(&mut self, program: &mut Program) -> Result<(), RuntimeError> {
+        loop {
+            match self.resume(program, usize::MAX, |_| false) {
+                Status::Finished => return Ok(()),
+                Status::Paused(_) => continue,
+                Status::Error(e) => return Err(e),
+                Status::NeedsInput => {
+                    let pos = *program
+                        .fetch_instruction()
+                        .expect("NeedsInput returned past the end of the program")
+                        .get_position();
+                    return Err(RuntimeError::Input(
+                        "Input is not available yet".to_string(),
+                        pos,
+                    ));
                 }
-                Operation::CellInc => self.tape.inc(instruction.get_n()),
-                Operation::CellDec => self.tape.dec(instruction.get_n()),
-                Operation::CellRead => self.handle_dot(),
-                Operation::CellWrite => {
-                    let mut buf: [u8; 1] = [0];
-                    self.input
-                        .read_exact(&mut buf[..])
-                        .map_err(|e| e.to_string())?;
-                    self.tape.set_current_value(Wrapping(buf[0].into()));
+            }
+        }
+    }
+
+    /// Executes at most `n` instructions and returns the state in which the program was left,
+    /// calling it again will continue from the point where it stopped.
+    /// Limits are counted from the first step until the program finishes or fails,
+    /// so the timeout also includes the time spent between calls.
+    pub fn step(&mut self, program: &mut Program, n: usize) -> Status {
+        self.resume(program, n, |_| false)
+    }
+
+    /// Runs the program until `breakpoint` returns true for the instruction that is about
+    /// to be executed, in which case it will pause right before it.
+    /// The first instruction is always executed so that calling it again continues
+    /// past the breakpoint it stopped on.
+    pub fn run_until<F: FnMut(&Instruction) -> bool>(
+        &mut self,
+        program: &mut Program,
+        breakpoint: F,
+    ) -> Status {
+        self.resume(program, usize::MAX, breakpoint)
+    }
+
+    /// Captures the state of the execution of the program,
+    /// which can later be restored with `restore`.
+    /// NOTE: the bits of a byte that Boolfuck has only partially read or printed aren't captured
+    pub fn snapshot(&self, program: &Program) -> Snapshot {
+        let mut procedures = program
+            .get_procedures()
+            .iter()
+            .map(|(&number, &addr)| (number, addr))
+            .collect::<Vec<_>>();
+        procedures.sort_unstable();
+        Snapshot {
+            instructions: program.instructions().len(),
+            program: program.fingerprint(),
+            pc: program.get_pc(),
+            stack: program.get_stack().to_vec(),
+            procedures,
+            calls: program.get_calls().to_vec(),
+            tape_position: self.tape.current_position,
+            tape: self.tape.cells().iter().map(|v| v.0).collect(),
+            storage: self.storage.0,
+            input_position: self.input_bytes,
+            output_position: self.output_bytes,
+            steps: self.steps,
+            threads: self.threads.iter().cloned().collect(),
+        }
+    }
+
+    /// Puts both the interpreter and the program in the state saved in the snapshot,
+    /// so that the execution can be continued with `execute` or `step`.
+    /// NOTE: the input is not touched, so it's up to the caller to skip
+    /// the first `snapshot.input_position` bytes if they are still there.
+    pub fn restore(&mut self, program: &mut Program, snapshot: &Snapshot) -> Result<(), String> {
+        if snapshot.instructions != program.instructions().len() {
+            return Err(format!(
+                "Snapshot was taken from a program with {} instructions, but this one has {}",
+                snapshot.instructions,
+                program.instructions().len()
+            ));
+        }
+        if snapshot.program != program.fingerprint() {
+            return Err("Snapshot was taken from a different program".to_string());
+        }
+        let mut positions = iter::once(snapshot.tape_position)
+            .chain(snapshot.threads.iter().map(|(_, position)| *position));
+        if let Some(max_tape_length) = self.options.limits.max_tape_length {
+            if positions.any(|position| position >= max_tape_length) {
+                return Err(format!("Tape is limited to {} cells", max_tape_length));
+            }
+        }
+        for (context, _) in &snapshot.threads {
+            program.check_context(context)?;
+        }
+        self.use_program_tape(program);
+        for (_, position) in &snapshot.threads {
+            self.tape.check_position(*position)?;
+        }
+        self.tape
+            .restore_cells(snapshot.tape_cells(), snapshot.tape_position)?;
+        program.set_procedures(
+            snapshot.procedures.iter().copied().collect(),
+            snapshot.calls.clone(),
+        )?;
+        program.set_state(snapshot.pc, snapshot.stack.clone())?;
+        self.storage = Wrapping(snapshot.storage);
+        self.started = None;
+        self.steps = snapshot.steps;
+        self.input_bytes = snapshot.input_position;
+        self.output_bytes = snapshot.output_position;
+        self.threads = snapshot.threads.iter().cloned().collect();
+        Ok(())
+    }
+
+    fn reset_counters(&mut self) {
+        self.started = None;
+        self.steps = 0;
+        self.input_bytes = 0;
+        self.output_bytes = 0;
+    }
+
+    fn resume<F: FnMut(&Instruction) -> bool>(
+        &mut self,
+        program: &mut Program,
+        max_steps: usize,
+        breakpoint: F,
+    ) -> Status {
+        self.use_program_tape(program);
+        let folded = self.apply_stored_prefix(program);
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+
+        let mut status = match folded {
+            Ok(()) => self.run_steps(program, max_steps, breakpoint),
+            Err(e) => Status::Error(e),
+        };
+        if status == Status::Finished && self.output_bits.1 > 0 {
+            // the last byte is padded with zeros
+            let byte = self.output_bits.0;
+            if let Err(e) = self.print(&[byte], program.current_position()) {
+                status = Status::Error(e);
+            }
+        }
+
+        // the caller gets the control back, e.g. to take a snapshot or to wait for the input,
+        // so everything printed so far should be visible,
+        // though the original error is more important than this one
+        let flushed = self.flush_output(program.current_position());
+        let status = match (status, flushed) {
+            (Status::Error(e), _) | (_, Err(e)) => Status::Error(e),
+            (status, Ok(())) => status,
+        };
+        if !matches!(status, Status::NeedsInput | Status::Paused(_)) {
+            self.reset_counters();
+            // threads of a program that has stopped because of an error would never finish
+            self.threads.clear();
+            self.input_bits = (0, 0);
+            self.output_bits = (0, 0);
+        }
+        status
+    }
+
+    fn run_steps<F: FnMut(&Instruction) -> bool>(
+        &mut self,
+        program: &mut Program,
+        max_steps: usize,
+        mut breakpoint: F,
+    ) -> Status {
+        let mut steps = 0;
+        loop {
+            let instruction = match program.fetch_instruction() {
+                Some(instruction) => instruction,
+                // this thread has finished, but the others may still be running
+                None if !self.threads.is_empty() => {
+                    self.next_thread(program, false);
+                    continue;
                 }
-                Operation::BeginLoop(_) => program.begin_loop(self.tape.current_value.0),
-                Operation::EndLoop => program.end_loop(self.tape.current_value.0),
+                None => return Status::Finished,
             };
-            program.inc_pc();
+            if steps >= max_steps || (steps > 0 && breakpoint(instruction)) {
+                return Status::Paused(steps);
+            }
+            match self.execute_instruction(program) {
+                Ok(()) => steps += 1,
+                Err(status) => return status,
+            }
+            self.next_thread(program, true);
+        }
+    }
+
+    /// Gives the turn to the first waiting thread,
+    /// the current one is put at the end of the queue if it's still running.
+    fn next_thread(&mut self, program: &mut Program, running: bool) {
+        let (mut context, position) = match self.threads.pop_front() {
+            Some(thread) => thread,
+            None => return,
+        };
+        program.swap_context(&mut context);
+        if running {
+            self.threads
+                .push_back((context, self.tape.current_position));
+        }
+        self.tape.set_position(position);
+    }
+
+    fn fork(&mut self, program: &Program, pos: Position) -> Result<(), Status> {
+        let position = self.tape.current_position;
+        let mut context = program.get_context();
+        // the child continues after `Y`
+        context.pc += 1;
+
+        self.tape.set_current_value(Wrapping(0));
+        self.move_pointer(1, true, pos).map_err(Status::Error)?;
+        self.tape.set_current_value(Wrapping(1));
+        self.threads
+            .push_back((context, self.tape.current_position));
+        self.tape.set_position(position);
+        Ok(())
+    }
+
+    /// Executes the instruction pointed to by the program counter and moves onto the next one,
+    /// unless the input isn't ready yet, in which case nothing changes.
+    fn execute_instruction(&mut self, program: &mut Program) -> Result<(), Status> {
+        let addr = program.get_pc();
+        let instruction = match program.fetch_instruction() {
+            Some(instruction) => instruction,
+            None => return Err(Status::Finished),
+        };
+        let n = instruction.get_n();
+        let pos = *instruction.get_position();
+        self.check_limits(pos).map_err(Status::Error)?;
+
+        match *instruction.get_op() {
+            Operation::TapeLeft => self.move_pointer(n, false, pos).map_err(Status::Error)?,
+            Operation::TapeRight => self.move_pointer(n, true, pos).map_err(Status::Error)?,
+            Operation::TapePrint => {
+                let tape = format!("!TAPE: {}", self.tape);
+                self.print(tape.as_bytes(), pos).map_err(Status::Error)?
+            }
+            Operation::CellInc => self.tape.inc(n),
+            Operation::CellDec => self.tape.dec(n),
+            Operation::CellRead => self.handle_dot(pos).map_err(Status::Error)?,
+            Operation::CellWrite => {
+                if let Some(byte) = self.read_byte(pos)? {
+                    self.tape.set_current_value(Wrapping(byte.into()));
+                }
+            }
+            Operation::BeginLoop(_) => program.begin_loop(self.tape.current_value.0),
+            Operation::EndLoop => program.end_loop(self.tape.current_value.0),
+            Operation::BeginProcedure(_) => program.define_procedure(self.tape.current_value.0),
+            Operation::EndProcedure => program.end_procedure(),
+            Operation::CallProcedure => program
+                .call_procedure(self.tape.current_value.0)
+                .map_err(|e| Status::Error(RuntimeError::Procedure(e, pos)))?,
+            Operation::Fork => self.fork(program, pos)?,
+            Operation::End => {
+                // all the other threads end as well
+                self.threads.clear();
+                program.end();
+            }
+            Operation::Store => self.storage = self.tape.current_value,
+            Operation::Retrieve => self.tape.set_current_value(self.storage),
+            Operation::ShiftRight => self.tape.set_current_value(self.tape.current_value >> 1),
+            Operation::ShiftLeft => self.tape.set_current_value(self.tape.current_value << 1),
+            Operation::Not => self.tape.set_current_value(!self.tape.current_value),
+            Operation::Xor => self
+                .tape
+                .set_current_value(self.tape.current_value ^ self.storage),
+            Operation::And => self
+                .tape
+                .set_current_value(self.tape.current_value & self.storage),
+            Operation::Or => self
+                .tape
+                .set_current_value(self.tape.current_value | self.storage),
+            Operation::BitRead => self.handle_bit_read(pos).map_err(Status::Error)?,
+            Operation::BitWrite => self.handle_bit_write(pos)?,
+        };
+        if let Some(coverage) = &mut self.coverage {
+            coverage.hit(addr);
         }
+        program.inc_pc();
+        self.steps += 1;
         Ok(())
     }
 }
@@ -99,6 +650,8 @@ mod interpreter_tests {
     use std::io::Cursor;
 
     use super::*;
+    use crate::parser::{dialect::Dialect, Parser};
+    use crate::tape::TapeMode;
 
     #[test]
     fn test_basic_io() {
@@ -121,4 +674,522 @@ mod interpreter_tests {
         interpreter.execute(&mut program).unwrap();
         assert_eq!(out, vec![0]);
     }
+
+    #[test]
+    fn test_step_limit() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        interpreter.set_limits(Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        });
+        let mut program = Parser::parse("+[]").unwrap();
+
+        assert_eq!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::StepLimitExceeded(Position {
+                line_number: 0,
+                char_number: 2
+            }))
+        );
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        interpreter.set_limits(Limits {
+            timeout: Some(std::time::Duration::from_millis(10)),
+            ..Limits::default()
+        });
+        let mut program = Parser::parse("+[]").unwrap();
+
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::TimeLimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_tape_limit() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        interpreter.set_limits(Limits {
+            max_tape_length: Some(3),
+            ..Limits::default()
+        });
+        let mut program = Parser::parse(">>\n>").unwrap();
+
+        assert_eq!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::TapeLimitExceeded(Position {
+                line_number: 1,
+                char_number: 0
+            }))
+        );
+
+        // on a wrapping tape going left can also reach cells past the limit
+        let mut interpreter = Interpreter::with_options(
+            Cursor::new(vec![]),
+            Vec::new(),
+            Options {
+                tape_mode: TapeMode::Wrapping(5),
+                limits: Limits {
+                    max_tape_length: Some(3),
+                    ..Limits::default()
+                },
+                ..Options::default()
+            },
+        );
+        let mut program = Parser::parse("><").unwrap();
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        let mut program = Parser::parse("<").unwrap();
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::TapeLimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        interpreter.set_limits(Limits {
+            max_output_bytes: Some(2),
+            ..Limits::default()
+        });
+        let mut program = Parser::parse("+++[.]").unwrap();
+
+        assert_eq!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::OutputLimitExceeded(Position {
+                line_number: 0,
+                char_number: 4
+            }))
+        );
+        assert_eq!(out, vec![3, 3]);
+
+        // !TAPE counts towards the limit as well
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        interpreter.set_limits(Limits {
+            max_output_bytes: Some(2),
+            ..Limits::default()
+        });
+        let mut program = Parser::parse("!TAPE").unwrap();
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::OutputLimitExceeded(_))
+        ));
+    }
+
+    /// Input that only has the bytes which were already "typed in",
+    /// asking for more will block.
+    struct PendingInput(Vec<u8>);
+
+    impl Read for PendingInput {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            buf[0] = self.0.remove(0);
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let mut program = Parser::parse("+[>+<+]").unwrap();
+
+        assert_eq!(interpreter.step(&mut program, 10), Status::Paused(10));
+        assert_eq!(interpreter.step(&mut program, 10), Status::Paused(10));
+        assert_eq!(interpreter.tape.current_value, Wrapping(4));
+
+        let mut program = Parser::parse("+.").unwrap();
+        assert_eq!(interpreter.step(&mut program, 10), Status::Finished);
+    }
+
+    #[test]
+    fn test_step_needs_input() {
+        let mut input = PendingInput(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let mut program = Parser::parse("+,.").unwrap();
+
+        assert_eq!(interpreter.step(&mut program, 10), Status::NeedsInput);
+        assert_eq!(interpreter.step(&mut program, 10), Status::NeedsInput);
+        interpreter.input.0.push(b'A');
+        assert_eq!(interpreter.step(&mut program, 10), Status::Finished);
+        assert_eq!(out, b"A");
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let mut program = Parser::parse("+++[.-]").unwrap();
+        let is_dot = |i: &Instruction| i.get_op() == &Operation::CellRead;
+
+        assert_eq!(
+            interpreter.run_until(&mut program, is_dot),
+            Status::Paused(2)
+        );
+        assert_eq!(
+            interpreter.run_until(&mut program, is_dot),
+            Status::Paused(3)
+        );
+        assert_eq!(
+            interpreter.run_until(&mut program, is_dot),
+            Status::Paused(3)
+        );
+        assert_eq!(
+            interpreter.run_until(&mut program, is_dot),
+            Status::Finished
+        );
+        assert_eq!(out, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_step_error() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let mut program = Parser::parse("+<").unwrap();
+
+        assert!(matches!(
+            interpreter.step(&mut program, 10),
+            Status::Error(RuntimeError::Tape(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_eof_policies() {
+        let run = |eof_policy| {
+            let mut interpreter = Interpreter::with_options(
+                &b""[..],
+                Vec::new(),
+                Options {
+                    eof_policy,
+                    cell_type: crate::tape::CellType::U8,
+                    output_mode: OutputMode::Numerical,
+                    ..Options::default()
+                },
+            );
+            let mut program = Parser::parse("+++,.").unwrap();
+            let result = interpreter.execute(&mut program);
+            (result, String::from_utf8(interpreter.output).unwrap())
+        };
+
+        assert!(matches!(
+            run(EofPolicy::Error).0,
+            Err(RuntimeError::Input(_, _))
+        ));
+        assert_eq!(run(EofPolicy::Zero), (Ok(()), "0\n".to_string()));
+        assert_eq!(run(EofPolicy::Max), (Ok(()), "255\n".to_string()));
+        assert_eq!(run(EofPolicy::Unchanged), (Ok(()), "3\n".to_string()));
+    }
+
+    struct Interrupted;
+
+    impl Read for Interrupted {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::Interrupted.into())
+        }
+    }
+
+    #[test]
+    fn test_interrupted_input() {
+        let mut interpreter = Interpreter::with_options(
+            Interrupted,
+            Vec::new(),
+            Options {
+                flush_policy: FlushPolicy::OnInput,
+                ..Options::default()
+            },
+        );
+        let mut program = Parser::parse("+.,").unwrap();
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::Input(_, _))
+        ));
+        assert_eq!(interpreter.output, vec![1]);
+    }
+
+    #[test]
+    fn test_output_modes() {
+        let run = |output_mode| {
+            let mut interpreter = Interpreter::with_options(
+                &b""[..],
+                Vec::new(),
+                Options {
+                    output_mode,
+                    ..Options::default()
+                },
+            );
+            let mut program = Parser::parse("++++++++++[>+++++++++++++++++++++<-]>.").unwrap();
+            interpreter.execute(&mut program).unwrap();
+            interpreter.output
+        };
+
+        assert_eq!(run(OutputMode::Char), "Ò".as_bytes());
+        assert_eq!(run(OutputMode::Byte), vec![210]);
+        assert_eq!(run(OutputMode::Numerical), b"210\n");
+    }
+
+    #[test]
+    fn test_invalid_character() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Parser::parse("-.").unwrap();
+
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::Output(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_run_source() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        assert_eq!(interpreter.run_source("++++++[>+++++++++++<-]>-."), Ok(()));
+        assert_eq!(interpreter.output, b"A");
+
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let error = interpreter.run_source("\n[").unwrap_err();
+        assert!(error.contains("line 2, char 1"), "{}", error);
+        let error = interpreter.run_source("\n <").unwrap_err();
+        assert!(error.contains("line 2, char 2"), "{}", error);
+    }
+
+    #[test]
+    fn test_pbrain_procedures() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        // procedure 1 prints the current cell plus 64, then clears it and calls
+        // procedure 0 which increments it back, the main program calls it twice
+        let src = "(+)+(>++++++++[<++++++++>-]<.[-]:)::>++++++++[<++++++++>-]<+.";
+        let mut program = Dialect::Pbrain.parse(src).unwrap();
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        assert_eq!(interpreter.output, b"AAB");
+        assert!(program.get_calls().is_empty());
+
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Pbrain.parse("(-)+:").unwrap();
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::Procedure(_, pos)) if pos.char_number == 4
+        ));
+    }
+
+    #[test]
+    fn test_extended_type_i() {
+        let mut interpreter = Interpreter::with_options(
+            &b""[..],
+            Vec::new(),
+            Options {
+                cell_type: crate::tape::CellType::U8,
+                output_mode: OutputMode::Byte,
+                ..Options::default()
+            },
+        );
+        // everything after `@` is skipped
+        let src = "+++{.$}.~.^.&.+|.!.@.";
+        let mut program = Dialect::ExtendedTypeI.parse(src).unwrap();
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        assert_eq!(interpreter.output, vec![6, 3, 252, 250, 2, 7, 6]);
+    }
+
+    #[test]
+    fn test_boolfuck_bit_io() {
+        // 'A' is 0b01000001 and bits are printed starting from the least significant one
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let src = "+;+;;;;;+;+;";
+        assert_eq!(
+            interpreter.run_dialect_source(src, "A", &Dialect::Boolfuck),
+            Ok(())
+        );
+        assert_eq!(interpreter.output, b"A");
+
+        let mut interpreter = Interpreter::new(&b"Hi"[..], Vec::new());
+        let src = ",;,;,;,;,;,;,;,;,;,;,;,;,;,;,;,;";
+        assert_eq!(
+            interpreter.run_dialect_source(src, "echo", &Dialect::Boolfuck),
+            Ok(())
+        );
+        assert_eq!(interpreter.output, b"Hi");
+
+        // partial byte at the end is padded with zeros
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        assert_eq!(
+            interpreter.run_dialect_source("+;;+;", "partial", &Dialect::Boolfuck),
+            Ok(())
+        );
+        assert_eq!(interpreter.output, vec![0b011]);
+    }
+
+    #[test]
+    fn test_smallfuck() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Smallfuck.parse("*>*>>**<*[>]").unwrap();
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        assert_eq!(interpreter.snapshot(&program).tape, vec![1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_dialect_cells_only_for_its_programs() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        assert_eq!(interpreter.run_source(">+"), Ok(()));
+        assert_eq!(
+            interpreter.run_dialect_source("++<+", "bits", &Dialect::Boolfuck),
+            Ok(())
+        );
+        // brainfuck is back on its own cells, and the tape it left
+        assert_eq!(interpreter.run_source("+."), Ok(()));
+        assert_eq!(interpreter.output, vec![2]);
+    }
+
+    #[test]
+    fn test_boolfuck_tape() {
+        // `+` flips the bit even without asking for bits and the tape goes on to the left
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Boolfuck.parse("+<<+<[+]").unwrap();
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        assert_eq!(interpreter.snapshot(&program).tape, vec![0, 1, 0, 1]);
+
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        interpreter.set_limits(Limits {
+            max_tape_length: Some(2),
+            ..Limits::default()
+        });
+        let mut program = Dialect::Boolfuck.parse("<<").unwrap();
+        assert_eq!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::TapeLimitExceeded(Position {
+                line_number: 0,
+                char_number: 0
+            }))
+        );
+    }
+
+    #[test]
+    fn test_brainfork_threads() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Brainfork.parse("Y..").unwrap();
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        // child sees 1 and goes first, then the threads take turns
+        assert_eq!(interpreter.output, vec![1, 0, 1, 0]);
+
+        // pausing doesn't change the order, and the tape is shared,
+        // so the parent prints the cell incremented by the child
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Brainfork.parse("Y[+<]>.").unwrap();
+        assert_eq!(interpreter.step(&mut program, 3), Status::Paused(3));
+        assert_eq!(interpreter.execute(&mut program), Ok(()));
+        assert_eq!(interpreter.output, vec![2, 2]);
+
+        // the child's pointer has to stay within the limit as well
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        interpreter.set_limits(Limits {
+            max_tape_length: Some(1),
+            ..Limits::default()
+        });
+        let mut program = Dialect::Brainfork.parse("Y").unwrap();
+        assert_eq!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::TapeLimitExceeded(Position {
+                line_number: 0,
+                char_number: 0
+            }))
+        );
+    }
+
+    /// Output that remembers when it was flushed.
+    #[derive(Default)]
+    struct FlushLog {
+        written: Vec<u8>,
+        flushes: Vec<Vec<u8>>,
+    }
+
+    impl Write for FlushLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes.push(self.written.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush_policies() {
+        let run = |flush_policy| {
+            let mut interpreter = Interpreter::builder(&b"x"[..], FlushLog::default())
+                .flush_policy(flush_policy)
+                .build();
+            // prints "a\nb", reads a character and prints "c"
+            let mut program = Parser::parse(
+                "++++++++++[>++++++++++<-]>---.<++++++++++.>+.,>++++++++++[>++++++++++<-]>-.",
+            )
+            .unwrap();
+            interpreter.execute(&mut program).unwrap();
+            interpreter.output.flushes
+        };
+
+        assert_eq!(
+            run(FlushPolicy::Byte),
+            vec![
+                b"a".to_vec(),
+                b"a\n".to_vec(),
+                b"a\nb".to_vec(),
+                b"a\nbc".to_vec()
+            ]
+        );
+        assert_eq!(
+            run(FlushPolicy::Newline),
+            vec![b"a\n".to_vec(), b"a\nb".to_vec(), b"a\nbc".to_vec()]
+        );
+        assert_eq!(
+            run(FlushPolicy::OnInput),
+            vec![b"a\nb".to_vec(), b"a\nbc".to_vec()]
+        );
+
+        // pausing gives the control back to the caller, so the output is written out as well
+        let mut interpreter = Interpreter::builder(&b""[..], FlushLog::default())
+            .flush_policy(FlushPolicy::OnInput)
+            .build();
+        let mut program = Parser::parse("+.+.").unwrap();
+        assert_eq!(interpreter.step(&mut program, 2), Status::Paused(2));
+        assert_eq!(interpreter.output.flushes, vec![vec![1]]);
+    }
+
+    /// Output that is already closed, like a pipe to `head` that has exited.
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let mut interpreter = Interpreter::new(&b""[..], BrokenPipe);
+        let mut program = Parser::parse("+[.]").unwrap();
+
+        assert!(matches!(
+            interpreter.execute(&mut program),
+            Err(RuntimeError::Output(_, _))
+        ));
+    }
 }
@@ -0,0 +1,56 @@
+use super::limits::Limits;
+use crate::tape::{CellType, TapeMode};
+
+/// What `,` does when there is nothing more to read.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum EofPolicy {
+    /// Stop the program with an error.
+    #[default]
+    Error,
+    /// Set the current cell to 0.
+    Zero,
+    /// Set the current cell to its maximum value, i.e. -1.
+    Max,
+    /// Leave the current cell as it is.
+    Unchanged,
+}
+
+/// How `.` prints the current cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    /// As the unicode character with that value.
+    #[default]
+    Char,
+    /// As a single byte, using only the lowest 8 bits of the value.
+    Byte,
+    /// As a decimal number followed by a newline.
+    Numerical,
+}
+
+/// When the output is actually written out.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FlushPolicy {
+    /// After every printed character.
+    Byte,
+    /// After every printed newline.
+    #[default]
+    Newline,
+    /// Only before reading the input and when the program stops.
+    OnInput,
+}
+
+/// Everything that can be configured about an interpreter.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Options {
+    pub cell_type: CellType,
+    pub eof_policy: EofPolicy,
+    pub tape_mode: TapeMode,
+    pub limits: Limits,
+    pub output_mode: OutputMode,
+    pub flush_policy: FlushPolicy,
+    /// If set, `run_source` and the like execute the part of the program before its first `,`
+    /// ahead of time, for at most this many steps, and store it with the program (see `Prefix`),
+    /// which then continues from where it stopped when it starts on a blank tape.
+    /// Programs are run as they are while coverage is recorded.
+    pub fold_prefix: Option<usize>,
+}
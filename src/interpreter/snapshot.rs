@@ -0,0 +1,328 @@
+use std::fmt::Display;
+use std::fs;
+use std::num::Wrapping;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::parser::{utils::Address, Context};
+
+const HEADER: &str = "biir snapshot 1";
+
+/// Full state of a program in the middle of its execution.
+/// It's stored as plain text, one `key values...` pair per line, e.g.:
+/// ```text
+/// biir snapshot 1
+/// instructions 7
+/// program 10210738580216347893
+/// pc 4
+/// stack 1
+/// procedures
+/// calls
+/// tape_position 1
+/// tape 2 3
+/// storage 0
+/// input_position 0
+/// output_position 0
+/// steps 9
+/// ```
+/// Every brainfork thread waiting for its turn is written as
+/// `thread tape_position pc stack_length stack... calls...`.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Snapshot {
+    /// number of instructions in the program, used to make sure that
+    /// a snapshot is restored into the same program it was taken from
+    pub instructions: usize,
+    /// `Program::fingerprint` of that program
+    pub program: u64,
+    pub pc: Address,
+    pub stack: Vec<Address>,
+    /// numbers of the defined procedures and addresses where they start, sorted by number
+    pub procedures: Vec<(usize, Address)>,
+    pub calls: Vec<Address>,
+    pub tape_position: usize,
+    pub tape: Vec<usize>,
+    /// storage cell of Extended Type I
+    pub storage: usize,
+    /// number of bytes already read from the input,
+    /// the input has to be positioned right after them before continuing
+    pub input_position: usize,
+    /// number of bytes already written to the output
+    pub output_position: usize,
+    pub steps: usize,
+    /// brainfork threads waiting for their turn, with the positions of their pointers
+    pub threads: Vec<(Context, usize)>,
+}
+
+impl Snapshot {
+    pub fn save<P: AsRef<Path>>(&self, file: P) -> Result<(), String> {
+        fs::write(file.as_ref(), self.to_string()).map_err(|e| {
+            format!(
+                "Error occured while saving snapshot to {}: {}",
+                file.as_ref().display(),
+                e
+            )
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(file: P) -> Result<Self, String> {
+        let src = fs::read_to_string(file.as_ref()).map_err(|e| {
+            format!(
+                "Error occured while reading snapshot {}: {}",
+                file.as_ref().display(),
+                e
+            )
+        })?;
+        src.parse()
+    }
+
+    pub(crate) fn tape_cells(&self) -> Vec<Wrapping<usize>> {
+        self.tape.iter().map(|v| Wrapping(*v)).collect()
+    }
+}
+
+fn join(values: &[usize]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "instructions {}", self.instructions)?;
+        writeln!(f, "program {}", self.program)?;
+        writeln!(f, "pc {}", self.pc)?;
+        writeln!(f, "stack {}", join(&self.stack))?;
+        let procedures = self
+            .procedures
+            .iter()
+            .flat_map(|&(number, addr)| [number, addr])
+            .collect::<Vec<_>>();
+        writeln!(f, "procedures {}", join(&procedures))?;
+        writeln!(f, "calls {}", join(&self.calls))?;
+        writeln!(f, "tape_position {}", self.tape_position)?;
+        writeln!(f, "tape {}", join(&self.tape))?;
+        writeln!(f, "storage {}", self.storage)?;
+        writeln!(f, "input_position {}", self.input_position)?;
+        writeln!(f, "output_position {}", self.output_position)?;
+        writeln!(f, "steps {}", self.steps)?;
+        for (context, position) in &self.threads {
+            let mut values = vec![*position, context.pc, context.stack.len()];
+            values.extend(&context.stack);
+            values.extend(&context.calls);
+            writeln!(f, "thread {}", join(&values))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("Not a biir snapshot".to_string());
+        }
+
+        let mut snapshot = Snapshot::default();
+        let mut keys = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let mut words = line.split_whitespace();
+            let key = match words.next() {
+                Some(key) => key,
+                None => continue,
+            };
+            keys.push(key);
+            if key == "program" {
+                snapshot.program = match (words.next().map(str::parse), words.next()) {
+                    (Some(Ok(hash)), None) => hash,
+                    _ => return Err(format!("Expected a single value for {}", key)),
+                };
+                continue;
+            }
+            let values = words
+                .map(|w| {
+                    w.parse::<usize>().map_err(|e| {
+                        // + 2 because of the header and indexing from 0
+                        format!("Invalid value on line {} of snapshot: {}", i + 2, e)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let single_value = || match values[..] {
+                [v] => Ok(v),
+                _ => Err(format!("Expected a single value for {}", key)),
+            };
+
+            match key {
+                "instructions" => snapshot.instructions = single_value()?,
+                "pc" => snapshot.pc = single_value()?,
+                "stack" => snapshot.stack = values.clone(),
+                "procedures" if values.len() % 2 == 0 => {
+                    snapshot.procedures = values.chunks(2).map(|p| (p[0], p[1])).collect()
+                }
+                "procedures" => return Err("Expected pairs of values for procedures".to_string()),
+                "calls" => snapshot.calls = values.clone(),
+                "tape_position" => snapshot.tape_position = single_value()?,
+                "tape" => snapshot.tape = values.clone(),
+                "storage" => snapshot.storage = single_value()?,
+                "input_position" => snapshot.input_position = single_value()?,
+                "output_position" => snapshot.output_position = single_value()?,
+                "steps" => snapshot.steps = single_value()?,
+                "thread" => match values[..] {
+                    [position, pc, stack_length, ref rest @ ..] if stack_length <= rest.len() => {
+                        let context = Context {
+                            pc,
+                            stack: rest[..stack_length].to_vec(),
+                            calls: rest[stack_length..].to_vec(),
+                        };
+                        snapshot.threads.push((context, position));
+                    }
+                    _ => return Err(format!("Invalid thread on line {} of snapshot", i + 2)),
+                },
+                _ => return Err(format!("Unknown key in snapshot: {}", key)),
+            }
+        }
+        // everything else can be empty, but without these the snapshot could be restored
+        // into any program and would start from its beginning
+        for key in ["program", "pc", "tape_position", "tape"] {
+            if !keys.contains(&key) {
+                return Err(format!("Missing {} in snapshot", key));
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::{dialect::Dialect, Parser};
+    use crate::tape::TapeMode;
+
+    #[test]
+    fn test_text_roundtrip() {
+        let snapshot = Snapshot {
+            instructions: 7,
+            program: 42,
+            pc: 4,
+            stack: vec![1, 3],
+            procedures: vec![(0, 2), (5, 0)],
+            calls: vec![6],
+            tape_position: 1,
+            tape: vec![2, 3],
+            storage: 4,
+            input_position: 5,
+            output_position: 6,
+            steps: 9,
+            threads: vec![(
+                Context {
+                    pc: 3,
+                    stack: vec![1],
+                    calls: vec![2, 0],
+                },
+                7,
+            )],
+        };
+        assert_eq!(snapshot.to_string().parse(), Ok(snapshot));
+    }
+
+    #[test]
+    fn test_invalid_snapshots() {
+        assert!("".parse::<Snapshot>().is_err());
+        assert!(HEADER.parse::<Snapshot>().is_err());
+        assert!(format!("{}\nprogram 1\npc 0\ntape", HEADER)
+            .parse::<Snapshot>()
+            .is_err());
+        assert!(format!("{}\npc 1 2", HEADER).parse::<Snapshot>().is_err());
+        assert!(format!("{}\npc a", HEADER).parse::<Snapshot>().is_err());
+        assert!(format!("{}\nfoo 1", HEADER).parse::<Snapshot>().is_err());
+        assert!(format!("{}\nprocedures 1", HEADER)
+            .parse::<Snapshot>()
+            .is_err());
+        assert!(format!("{}\nthread 0 1 2 3", HEADER)
+            .parse::<Snapshot>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_continue_from_snapshot() {
+        let src = ",[>+++<-]>.";
+        let mut input = Cursor::new(vec![4]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let mut program = Parser::parse(src).unwrap();
+        interpreter.step(&mut program, 7);
+        let snapshot: Snapshot = interpreter.snapshot(&program).to_string().parse().unwrap();
+        assert_eq!(snapshot.input_position, 1);
+
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let mut program = Parser::parse(src).unwrap();
+        interpreter.restore(&mut program, &snapshot).unwrap();
+        interpreter.execute(&mut program).unwrap();
+        assert_eq!(out, vec![12]);
+    }
+
+    #[test]
+    fn test_continue_threads_from_snapshot() {
+        let src = "Y[+<]>.";
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Brainfork.parse(src).unwrap();
+        interpreter.step(&mut program, 3);
+        let snapshot: Snapshot = interpreter.snapshot(&program).to_string().parse().unwrap();
+        assert_eq!(snapshot.threads.len(), 1);
+
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Dialect::Brainfork.parse(src).unwrap();
+        interpreter.restore(&mut program, &snapshot).unwrap();
+        interpreter.execute(&mut program).unwrap();
+        assert_eq!(interpreter.output, vec![2, 2]);
+    }
+
+    #[test]
+    fn test_restore_malformed_state() {
+        let mut interpreter = Interpreter::new(&b""[..], Vec::new());
+        let mut program = Parser::parse("+[-]").unwrap();
+        let mut snapshot = interpreter.snapshot(&program);
+        snapshot.tape = vec![5];
+
+        // `]` of a loop that was never entered
+        snapshot.pc = 3;
+        assert!(interpreter.restore(&mut program, &snapshot).is_err());
+        snapshot.stack = vec![1];
+        assert_eq!(interpreter.restore(&mut program, &snapshot), Ok(()));
+        // and a loop that was already left
+        snapshot.pc = 4;
+        assert!(interpreter.restore(&mut program, &snapshot).is_err());
+
+        snapshot.stack = Vec::new();
+        snapshot.tape_position = 100;
+        let mut interpreter = Interpreter::builder(&b""[..], Vec::new())
+            .tape_mode(TapeMode::Wrapping(4))
+            .build();
+        assert!(interpreter.restore(&mut program, &snapshot).is_err());
+        snapshot.tape_position = 3;
+        assert_eq!(interpreter.restore(&mut program, &snapshot), Ok(()));
+    }
+
+    #[test]
+    fn test_restore_into_different_program() {
+        let mut input = Cursor::new(vec![]);
+        let mut out: Vec<u8> = Vec::new();
+        let mut interpreter = Interpreter::new(&mut input, &mut out);
+        let program = Parser::parse("+[-]").unwrap();
+        let snapshot = interpreter.snapshot(&program);
+
+        let mut program = Parser::parse("+").unwrap();
+        assert!(interpreter.restore(&mut program, &snapshot).is_err());
+        let mut program = Parser::parse("-[+]").unwrap();
+        assert!(interpreter.restore(&mut program, &snapshot).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+use super::error::RuntimeError;
+
+/// State in which the execution was left after calling
+/// [`step`](super::Interpreter::step) or [`run_until`](super::Interpreter::run_until).
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    /// There are no more instructions to execute.
+    Finished,
+    /// The next instruction is `,` but the input has nothing to offer yet
+    /// (it returned `WouldBlock`), calling `step` again will retry reading.
+    NeedsInput,
+    /// Execution was paused after this many steps and can be resumed.
+    Paused(usize),
+    Error(RuntimeError),
+}
@@ -2,3 +2,5 @@
 pub mod interpreter;
 pub mod parser;
 pub mod tape;
+pub mod terminal;
+pub mod testing;
@@ -1,20 +1,112 @@
-use clap::{App, Arg};
+use clap::{value_t, App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use colored::*;
+use std::io::{Cursor, Read, Write};
 use std::process;
+use std::time::Duration;
+use std::{fs, io};
 
-use biir::interpreter::Interpreter;
+use biir::interpreter::{
+    coverage::Coverage,
+    limits::Limits,
+    options::{EofPolicy, FlushPolicy, Options, OutputMode},
+    Interpreter,
+};
+use biir::parser::{
+    dialect::Dialect, format, lint, minify, substitution::SubstitutionDialect, Parser, Program,
+};
+use biir::tape::CellType;
+use biir::terminal::{RawInput, RawOutput};
+use biir::testing::{self, Outcome, TestCase};
 
-fn main() {
-    let args = App::new("BIIR")
-        .version("2.0.0")
-        .author("Maks Rawski <maksymilian.rawski@tutanota.com>")
-        .about("Brainfuck Interpreter In Rust")
-        .arg(Arg::with_name("file")
-            .required(true)
+/// Reads an optional numerical argument, exiting with a nice message if it's not a number.
+fn optional_number(args: &ArgMatches, name: &str) -> Option<usize> {
+    if !args.is_present(name) {
+        return None;
+    }
+    match value_t!(args, name, usize) {
+        Ok(n) => Some(n),
+        Err(e) => e.exit(),
+    }
+}
+
+/// Returns the name and the source of the program, which can come from a file,
+/// `-e` argument or stdin.
+fn read_program(args: &ArgMatches) -> Result<(String, String), String> {
+    match (args.value_of("execute"), args.value_of("file")) {
+        (Some(code), _) => Ok(("source".to_string(), code.to_string())),
+        (None, Some("-")) => {
+            let mut src = String::new();
+            io::stdin()
+                .read_to_string(&mut src)
+                .map_err(|e| format!("Error occured while reading {}: {}", "stdin".bold(), e))?;
+            Ok(("stdin".to_string(), src))
+        }
+        (None, Some(file)) => {
+            let src = fs::read_to_string(file)
+                .map_err(|e| format!("Error occured while reading {}: {}", file.bold(), e))?;
+            Ok((file.to_string(), src))
+        }
+        (None, None) => unreachable!("clap makes sure that the program was given"),
+    }
+}
+
+/// Reads the dialect given by its name in the `name` argument or defined in a file
+/// given in `file_name`, if neither was given it's guessed from the extension of the file.
+fn read_dialect(args: &ArgMatches, name: &str, file_name: &str) -> Option<Dialect> {
+    match (args.value_of(name), args.value_of(file_name)) {
+        (Some(dialect), _) => Some(dialect.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        })),
+        (None, Some(file)) => match SubstitutionDialect::load(file) {
+            Ok(dialect) => Some(Dialect::Substitution(dialect)),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        (None, None) => args.value_of("file").and_then(Dialect::from_path),
+    }
+}
+
+/// Adds the arguments which choose the program: a file or `-e CODE`.
+fn program_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(Arg::with_name("file").help("File with the program, use `-` to read it from stdin."))
+        .arg(
+            Arg::with_name("execute")
+                .short("e")
+                .long("execute")
+                .value_name("CODE")
+                .help("Uses CODE instead of a program from a file."),
         )
-        .arg(Arg::with_name("debug")
-            .short("d")
-            .long("debug-mode")
-            .help("Allows the usage of `!TAPE` to print 10 nearby tape values,\nwill also print some debug info at the end.")
+        .group(
+            ArgGroup::with_name("program")
+                .args(&["file", "execute"])
+                .required(true),
+        )
+}
+
+/// Adds the arguments which decide how the program is run, shared by running and testing.
+fn mode_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app
+        .arg(Arg::with_name("dialect")
+            .long("dialect")
+            .value_name("DIALECT")
+            .help("Language of the program: bf, ook, pbrain, brainfork, extended, boolfuck, smallfuck,
+alphuck, pikalang or blub.\nBy default it's guessed from the extension of the file, otherwise brainfuck is used.")
+        )
+        .arg(Arg::with_name("dialect file")
+            .long("dialect-file")
+            .value_name("FILE")
+            .conflicts_with("dialect")
+            .help("Uses a dialect defined in a TOML FILE, which maps each of `><+-.,[]` to a word.")
+        )
+        .arg(Arg::with_name("eof")
+            .long("eof")
+            .value_name("POLICY")
+            .possible_values(&["error", "zero", "max", "unchanged"])
+            .default_value("error")
+            .help("What `,` does once there is nothing more to read.")
         )
         .arg(Arg::with_name("numerical")
             .short("n")
@@ -26,19 +118,475 @@ fn main() {
             .long("big-int-mode")
             .help("Uses raw usize for storing cell value instead of u8 with wrapping.\nWorks only when numerical mode is used!")
         )
-        .get_matches();
+        .arg(Arg::with_name("max steps")
+            .long("max-steps")
+            .value_name("N")
+            .help("Stops the program after executing N instructions.")
+        )
+        .arg(Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("MILLISECONDS")
+            .help("Stops the program once it has been running for that long.")
+        )
+        .arg(Arg::with_name("max tape length")
+            .long("max-tape-length")
+            .value_name("N")
+            .help("Stops the program when it tries to go beyond the N-th cell.")
+        )
+        .arg(Arg::with_name("max output bytes")
+            .long("max-output-bytes")
+            .value_name("N")
+            .help("Stops the program when it tries to print more than N bytes.")
+        )
+        .arg(Arg::with_name("fold prefix")
+            .long("fold-prefix")
+            .value_name("N")
+            .help("Executes up to N instructions before the first `,` ahead of time and continues from there.")
+        )
+        .arg(Arg::with_name("coverage")
+            .long("coverage")
+            .value_name("FILE")
+            .help("Writes to FILE how many times each line and loop of the program was executed.")
+        )
+        .arg(Arg::with_name("coverage format")
+            .long("coverage-format")
+            .value_name("FORMAT")
+            .possible_values(&["lcov", "annotated"])
+            .default_value("lcov")
+            .help("Format of the coverage report: LCOV tracefile or the source annotated with the counts.")
+        )
+}
 
-    let file = args.value_of("file").unwrap();
-    if args.is_present("big int") {
+/// Reads the options set with the arguments added by `mode_args`.
+fn read_options(args: &ArgMatches) -> Options {
+    if args.is_present("big int") && !args.is_present("numerical") {
         eprintln!("Big int mode is only available when using --numerical-mode");
         process::exit(1);
     }
+    Options {
+        cell_type: if args.is_present("big int") {
+            CellType::Usize
+        } else {
+            CellType::U8
+        },
+        eof_policy: match args.value_of("eof") {
+            Some("zero") => EofPolicy::Zero,
+            Some("max") => EofPolicy::Max,
+            Some("unchanged") => EofPolicy::Unchanged,
+            _ => EofPolicy::Error,
+        },
+        output_mode: if args.is_present("numerical") {
+            OutputMode::Numerical
+        } else {
+            OutputMode::Char
+        },
+        limits: Limits {
+            max_steps: optional_number(args, "max steps"),
+            timeout: optional_number(args, "timeout").map(|ms| Duration::from_millis(ms as u64)),
+            max_tape_length: optional_number(args, "max tape length"),
+            max_output_bytes: optional_number(args, "max output bytes"),
+        },
+        fold_prefix: optional_number(args, "fold prefix"),
+        ..Options::default()
+    }
+}
+
+/// Coverage report in the format chosen with `--coverage-format`.
+fn coverage_report(
+    args: &ArgMatches,
+    coverage: &Coverage,
+    program: &Program,
+    src: &str,
+    name: &str,
+) -> String {
+    match args.value_of("coverage format") {
+        Some("annotated") => coverage.annotate(program, src),
+        _ => coverage.lcov(program, name),
+    }
+}
+
+fn write_coverage(args: &ArgMatches, report: &str) {
+    let file = args
+        .value_of("coverage")
+        .expect("coverage is only recorded when the file was given");
+    if let Err(e) = fs::write(file, report) {
+        eprintln!("Error occured while writing {}: {}", file.bold(), e);
+        process::exit(1);
+    }
+}
+
+fn translate(args: &ArgMatches) {
+    let (name, src) = read_program(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let from = read_dialect(args, "from", "from file").unwrap_or(Dialect::Brainfuck);
+    let to = read_dialect(args, "to", "to file").expect("clap makes sure that --to was given");
+
+    match from.translate(&src, &to, !args.is_present("drop comments")) {
+        Ok(translated) => print!("{}", translated),
+        Err(e) => {
+            eprintln!("Error occured while translating {}: {}", name.bold(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn fmt(args: &ArgMatches) {
+    let (name, src) = read_program(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let width = optional_number(args, "width").unwrap_or(format::DEFAULT_WIDTH);
+    let formatted = format::format(&src, width).unwrap_or_else(|e| {
+        eprintln!("Error occured while formatting {}: {}", name.bold(), e);
+        process::exit(1);
+    });
+
+    if args.is_present("check") {
+        if formatted != src {
+            eprintln!("{} isn't formatted", name.bold());
+            process::exit(1);
+        }
+        return;
+    }
+    match (args.value_of("execute"), args.value_of("file")) {
+        (None, Some(file)) if file != "-" => {
+            if let Err(e) = fs::write(file, formatted) {
+                eprintln!("Error occured while writing {}: {}", file.bold(), e);
+                process::exit(1);
+            }
+        }
+        _ => print!("{}", formatted),
+    }
+}
+
+fn minify(args: &ArgMatches) {
+    let (name, src) = read_program(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    match minify::minify(&src) {
+        Ok(minified) => println!("{}", minified),
+        Err(e) => {
+            eprintln!("Error occured while parsing {}: {}", name.bold(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn lint(args: &ArgMatches) {
+    let (name, src) = read_program(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let dialect = read_dialect(args, "dialect", "dialect file").unwrap_or(Dialect::Brainfuck);
+    let program = dialect.parse(&src).unwrap_or_else(|e| {
+        eprintln!("Error occured while parsing {}: {}", name.bold(), e);
+        process::exit(1);
+    });
 
-    let i = &mut std::io::stdin();
-    let o = &mut std::io::stdout();
-    let mut interpreter = Interpreter::new(i, o);
+    let warnings = lint::lint(&program, args.is_present("underflow"));
+    for warning in &warnings {
+        eprintln!("{}: {}", "warning".yellow().bold(), warning);
+    }
+    if !warnings.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn test(args: &ArgMatches) {
+    let options = read_options(args);
+    let dialect = read_dialect(args, "dialect", "dialect file");
+    let mut cases = Vec::new();
+    for path in args
+        .values_of("paths")
+        .expect("clap makes sure that paths were given")
+    {
+        cases.extend(
+            testing::discover(path, dialect.as_ref()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            }),
+        );
+    }
+    if cases.is_empty() {
+        eprintln!("No programs with expected output were found");
+        process::exit(1);
+    }
+
+    let mut failures = Vec::new();
+    // coverage of every program, added up if it was given more than once
+    let mut coverages: Vec<(&TestCase, Coverage)> = Vec::new();
+    for case in &cases {
+        let name = case.path.display().to_string();
+        let (outcome, coverage) = case.run(options, args.is_present("coverage"));
+        if let Some(coverage) = coverage {
+            match coverages.iter_mut().find(|(c, _)| c.path == case.path) {
+                Some((_, total)) => total.merge(&coverage),
+                None => coverages.push((case, coverage)),
+            }
+        }
+        if outcome == Outcome::Passed {
+            println!("test {} ... {}", name, "ok".green());
+        } else {
+            println!("test {} ... {}", name, "FAILED".red());
+            failures.push((name, case, outcome));
+        }
+    }
+    if args.is_present("coverage") {
+        let mut report = String::new();
+        for (case, coverage) in &coverages {
+            let name = case.path.display().to_string();
+            if let Ok(program) = case.dialect.parse(&case.src) {
+                if args.value_of("coverage format") == Some("annotated") {
+                    report.push_str(&format!("{}:\n", name));
+                }
+                report.push_str(&coverage_report(args, coverage, &program, &case.src, &name));
+            }
+        }
+        write_coverage(args, &report);
+    }
+
+    for (name, case, outcome) in &failures {
+        println!("\n---- {} ----", name.bold());
+        match outcome {
+            Outcome::Error(e) => println!("{}", e),
+            Outcome::WrongOutput(output) => {
+                println!("{}\n{}", "--- expected".red(), "+++ actual".green());
+                let expected = String::from_utf8_lossy(&case.expected);
+                let output = String::from_utf8_lossy(output);
+                for (change, line) in testing::diff(&expected, &output) {
+                    let line = format!("{}{}", change, line);
+                    match change {
+                        '-' => println!("{}", line.red()),
+                        '+' => println!("{}", line.green()),
+                        _ => println!("{}", line),
+                    }
+                }
+            }
+            Outcome::Passed => unreachable!("only failures are kept"),
+        }
+    }
+
+    let passed = cases.len() - failures.len();
+    if failures.is_empty() {
+        println!("\ntest result: {}. {} passed", "ok".green(), passed);
+    } else {
+        println!(
+            "\ntest result: {}. {} passed; {} failed",
+            "FAILED".red(),
+            passed,
+            failures.len()
+        );
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let app = App::new("BIIR")
+        .version("2.0.0")
+        .author("Maks Rawski <maksymilian.rawski@tutanota.com>")
+        .about("Brainfuck Interpreter In Rust")
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(program_args(SubCommand::with_name("translate"))
+            .about("Translates the program from one dialect to another.")
+            .arg(Arg::with_name("from")
+                .long("from")
+                .value_name("DIALECT")
+                .help("Dialect of the program, by default it's guessed from the extension of the file,\notherwise brainfuck is used.")
+            )
+            .arg(Arg::with_name("from file")
+                .long("from-file")
+                .value_name("FILE")
+                .conflicts_with("from")
+                .help("Translates from a dialect defined in a TOML FILE.")
+            )
+            .arg(Arg::with_name("to")
+                .long("to")
+                .value_name("DIALECT")
+                .help("Dialect to translate the program into.")
+            )
+            .arg(Arg::with_name("to file")
+                .long("to-file")
+                .value_name("FILE")
+                .help("Translates into a dialect defined in a TOML FILE.")
+            )
+            .group(ArgGroup::with_name("target")
+                .args(&["to", "to file"])
+                .required(true)
+            )
+            .arg(Arg::with_name("drop comments")
+                .long("drop-comments")
+                .help("Leaves only the code, by default comments are kept\n(without anything that would be code in the new dialect).")
+            )
+        )
+        .subcommand(program_args(SubCommand::with_name("fmt"))
+            .about("Formats brainfuck source code, a file is rewritten in place,\notherwise the result is printed.")
+            .arg(Arg::with_name("check")
+                .long("check")
+                .help("Only checks whether the program is formatted, exits with 1 if it isn't.")
+            )
+            .arg(Arg::with_name("width")
+                .long("width")
+                .value_name("N")
+                .help("Maximum length of a line, 80 by default.")
+            )
+        )
+        .subcommand(program_args(SubCommand::with_name("minify"))
+            .about("Prints the shortest equivalent brainfuck program, without comments,\noperations which cancel out and loops which can never be entered.")
+        )
+        .subcommand(program_args(SubCommand::with_name("lint"))
+            .about("Warns about common mistakes, exits with 1 if any were found.")
+            .arg(Arg::with_name("underflow")
+                .long("underflow")
+                .help("Also warns about moves which may go to the left of the first cell,\ndepending on the values of cells.")
+            )
+            .arg(Arg::with_name("dialect")
+                .long("dialect")
+                .value_name("DIALECT")
+                .help("Language of the program, by default it's guessed from the extension of the file,\notherwise brainfuck is used.")
+            )
+            .arg(Arg::with_name("dialect file")
+                .long("dialect-file")
+                .value_name("FILE")
+                .conflicts_with("dialect")
+                .help("Uses a dialect defined in a TOML FILE.")
+            )
+        )
+        .subcommand(mode_args(SubCommand::with_name("test"))
+            .about("Runs the programs and compares what they print with the expected output,\nexits with 1 if any of them failed.\nThe expected output is taken from a `.out` file next to the program or from its first line\nwritten as `[expected: output]`, the input from an `.in` file.")
+            .arg(Arg::with_name("paths")
+                .value_name("PATH")
+                .multiple(true)
+                .required(true)
+                .help("Programs or directories which are searched for them.")
+            )
+        );
+    let args = mode_args(program_args(app))
+        .arg(Arg::with_name("bang input")
+            .long("bang-input")
+            .help("Treats everything after the first `!` (that isn't `!TAPE`) as the input.")
+        )
+        .arg(Arg::with_name("input")
+            .long("input")
+            .value_name("FILE")
+            .help("Reads the input of the program from FILE instead of stdin.")
+        )
+        .arg(Arg::with_name("input string")
+            .long("input-string")
+            .value_name("STR")
+            .help("Uses STR as the input of the program instead of stdin.")
+        )
+        .arg(Arg::with_name("output")
+            .long("output")
+            .value_name("FILE")
+            .help("Writes the output of the program to FILE instead of stdout.")
+        )
+        .arg(Arg::with_name("raw")
+            .short("r")
+            .long("raw")
+            .help("Puts the terminal in raw mode, so that `,` gets every key as soon as it's pressed.\nCtrl-C stops the program at the next `,` and Ctrl-D ends the input.")
+        )
+        .group(ArgGroup::with_name("input source")
+            .args(&["bang input", "input", "input string", "raw"])
+        )
+        .arg(Arg::with_name("flush")
+            .long("flush")
+            .value_name("POLICY")
+            .possible_values(&["byte", "newline", "input"])
+            .default_value("newline")
+            .help("When the output is written out: after every character, after every newline\nor only before reading the input and at the end.")
+        )
+        .arg(Arg::with_name("debug")
+            .short("d")
+            .long("debug-mode")
+            .help("Allows the usage of `!TAPE` to print 10 nearby tape values,\nwill also print some debug info at the end.")
+        )
+        .get_matches();
+
+    match args.subcommand() {
+        ("translate", Some(args)) => translate(args),
+        ("fmt", Some(args)) => fmt(args),
+        ("minify", Some(args)) => minify(args),
+        ("lint", Some(args)) => lint(args),
+        ("test", Some(args)) => test(args),
+        _ => run(&args),
+    }
+}
+
+fn run(args: &ArgMatches) {
+    let (name, src) = read_program(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let (src, input): (&str, Box<dyn Read>) = if args.is_present("bang input") {
+        let (src, input) = Parser::split_input(&src);
+        (src, Box::new(Cursor::new(input.as_bytes().to_vec())))
+    } else if let Some(input) = args.value_of("input string") {
+        (&src, Box::new(Cursor::new(input.as_bytes().to_vec())))
+    } else if let Some(file) = args.value_of("input") {
+        match fs::File::open(file) {
+            Ok(f) => (&src, Box::new(f)),
+            Err(e) => {
+                eprintln!("Error occured while opening {}: {}", file.bold(), e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("raw") {
+        match RawInput::new() {
+            Ok(raw_input) => (&src, Box::new(raw_input)),
+            Err(e) => {
+                eprintln!("Couldn't put the terminal in raw mode: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        (&src, Box::new(io::stdin()))
+    };
+    let output: Box<dyn Write> = match args.value_of("output") {
+        Some(file) => match fs::File::create(file) {
+            Ok(f) => Box::new(f),
+            Err(e) => {
+                eprintln!("Error occured while creating {}: {}", file.bold(), e);
+                process::exit(1);
+            }
+        },
+        None if args.is_present("raw") && termion::is_tty(&io::stdout()) => {
+            Box::new(RawOutput(io::stdout()))
+        }
+        None => Box::new(io::stdout()),
+    };
+
+    let options = Options {
+        flush_policy: match args.value_of("flush") {
+            Some("byte") => FlushPolicy::Byte,
+            Some("input") => FlushPolicy::OnInput,
+            _ => FlushPolicy::Newline,
+        },
+        ..read_options(args)
+    };
+    let mut interpreter = Interpreter::with_options(input, output, options);
+    if args.is_present("coverage") {
+        interpreter.record_coverage();
+    }
+
+    let dialect = read_dialect(args, "dialect", "dialect file").unwrap_or(Dialect::Brainfuck);
+    let result = interpreter.run_dialect_source(src, &name, &dialect);
+    let coverage = interpreter.take_coverage();
+    // process::exit doesn't run destructors, but the terminal may have to be restored
+    drop(interpreter);
+
+    // coverage of a program that failed is still useful for finding out why
+    if let (Some(coverage), Ok(program)) = (coverage, dialect.parse(src)) {
+        write_coverage(
+            args,
+            &coverage_report(args, &coverage, &program, src, &name),
+        );
+    }
 
-    if let Err(e) = interpreter.run(file) {
+    if let Err(e) = result {
         eprintln!("{}", e);
         process::exit(1);
     }
@@ -0,0 +1,197 @@
+//! Abstract interpretation of the pointer, which finds moves to the left of the first cell
+//! without running the program.
+//!
+//! Every instruction is given the range of offsets from the first cell the pointer
+//! can be at, loops are repeated until the range stops changing (growing bounds are
+//! widened straight to infinity), so balanced loops keep it exact.
+
+use std::collections::BTreeMap;
+
+use super::{instruction::Operation, utils::Position, Program};
+use crate::tape::TapeMode;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Underflow {
+    /// Whenever this move is executed the pointer goes to the left of the first cell.
+    Guaranteed(Position),
+    /// The pointer may go to the left of the first cell, depending on the values of cells.
+    Possible(Position),
+}
+
+impl Underflow {
+    pub fn position(&self) -> &Position {
+        match self {
+            Underflow::Guaranteed(pos) | Underflow::Possible(pos) => pos,
+        }
+    }
+}
+
+/// Offsets the pointer can be at, None means that there is no bound.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Offsets {
+    min: Option<isize>,
+    max: Option<isize>,
+}
+
+impl Offsets {
+    const UNKNOWN: Offsets = Offsets {
+        min: None,
+        max: None,
+    };
+
+    fn shift(self, n: isize) -> Self {
+        Offsets {
+            min: self.min.map(|min| min + n),
+            max: self.max.map(|max| max + n),
+        }
+    }
+
+    fn join(self, other: Self) -> Self {
+        Offsets {
+            min: self.min.zip(other.min).map(|(a, b)| a.min(b)),
+            max: self.max.zip(other.max).map(|(a, b)| a.max(b)),
+        }
+    }
+
+    /// Bounds that have moved since `self` are dropped, so that loops are analyzed
+    /// in a few iterations, no matter how many times they would run.
+    fn widen(self, joined: Self) -> Self {
+        Offsets {
+            min: self.min.filter(|_| joined.min == self.min),
+            max: self.max.filter(|_| joined.max == self.max),
+        }
+    }
+}
+
+struct Analysis<'a> {
+    program: &'a Program,
+    /// underflows found so far by the addresses of their instructions
+    underflows: BTreeMap<usize, Underflow>,
+}
+
+impl Analysis<'_> {
+    /// Goes through instructions from `start` up to `end` (exclusive) with the pointer
+    /// being at `offsets`, underflows are only recorded when `record` is set,
+    /// as while a loop is being analyzed the offsets are still incomplete.
+    fn run(&mut self, start: usize, end: usize, mut offsets: Offsets, record: bool) -> Offsets {
+        let instructions = self.program.instructions();
+        let mut addr = start;
+        while addr < end {
+            let i = &instructions[addr];
+            let n = i.get_n() as isize;
+            match i.get_op() {
+                Operation::TapeRight => offsets = offsets.shift(n),
+                Operation::TapeLeft => {
+                    offsets = offsets.shift(-n);
+                    let underflow = match offsets {
+                        Offsets { max: Some(max), .. } if max < 0 => {
+                            Some(Underflow::Guaranteed(*i.get_position()))
+                        }
+                        Offsets { min: Some(min), .. } if min >= 0 => None,
+                        _ => Some(Underflow::Possible(*i.get_position())),
+                    };
+                    // the program stops if it goes negative, so after a possible underflow
+                    // it can only continue from the first cell or further,
+                    // after a guaranteed one offsets are left as they are to not cause more warnings
+                    if let Some(Underflow::Possible(_)) = underflow {
+                        offsets.min = Some(0);
+                    }
+                    if let (true, Some(underflow)) = (record, underflow) {
+                        self.underflows.insert(addr, underflow);
+                    }
+                }
+                Operation::BeginLoop(Some(loop_end)) => {
+                    offsets = self.run_loop(addr + 1, *loop_end, offsets, record);
+                    addr = *loop_end;
+                }
+                // bodies of procedures are skipped, which one is called depends on the cell
+                Operation::BeginProcedure(Some(procedure_end)) => addr = *procedure_end,
+                Operation::CallProcedure => offsets = Offsets::UNKNOWN,
+                _ => {}
+            }
+            addr += 1;
+        }
+        offsets
+    }
+
+    fn run_loop(&mut self, start: usize, end: usize, entry: Offsets, record: bool) -> Offsets {
+        let mut offsets = entry;
+        loop {
+            let joined = offsets.join(self.run(start, end, offsets, false));
+            if joined == offsets {
+                break;
+            }
+            offsets = offsets.widen(joined);
+        }
+        if record {
+            self.run(start, end, offsets, true);
+        }
+        offsets
+    }
+}
+
+/// Finds all the moves which are guaranteed or possible to go to the left of the first cell.
+/// There are none when the program's dialect uses an infinite or wrapping tape.
+/// NOTE: bodies of procedures aren't analyzed, calling one makes the pointer unknown
+pub fn tape_underflows(program: &Program) -> Vec<Underflow> {
+    if let Some(TapeMode::Infinite) | Some(TapeMode::Wrapping(_)) = program.tape_mode() {
+        return Vec::new();
+    }
+    let mut analysis = Analysis {
+        program,
+        underflows: BTreeMap::new(),
+    };
+    let start = Offsets {
+        min: Some(0),
+        max: Some(0),
+    };
+    analysis.run(0, program.instructions().len(), start, true);
+    analysis.underflows.into_values().collect()
+}
+
+#[cfg(test)]
+mod analysis_tests {
+    use super::*;
+    use crate::parser::{dialect::Dialect, Parser};
+
+    fn underflows(src: &str) -> Vec<Underflow> {
+        tape_underflows(&Parser::parse(src).unwrap())
+    }
+
+    fn pos(char_number: usize) -> Position {
+        Position {
+            line_number: 0,
+            char_number,
+        }
+    }
+
+    #[test]
+    fn test_straight_line() {
+        assert_eq!(underflows(">>+<<-"), vec![]);
+        assert_eq!(underflows(">+<<"), vec![Underflow::Guaranteed(pos(2))]);
+    }
+
+    #[test]
+    fn test_balanced_loops() {
+        assert_eq!(underflows("++[>++[>+<-]<-]"), vec![]);
+        assert_eq!(underflows("++[>+<-]<"), vec![Underflow::Guaranteed(pos(8))]);
+        assert_eq!(underflows("+[<+>-]"), vec![Underflow::Guaranteed(pos(2))]);
+    }
+
+    #[test]
+    fn test_unbalanced_loops() {
+        // going right any number of times and coming back one cell is always fine
+        assert_eq!(underflows(">+[>+]<"), vec![]);
+        assert_eq!(underflows(">+[>+]<<<"), vec![Underflow::Possible(pos(6))]);
+        assert_eq!(underflows(">>+[<]"), vec![Underflow::Possible(pos(4))]);
+    }
+
+    #[test]
+    fn test_tape_modes_and_procedures() {
+        let boolfuck = Dialect::Boolfuck.parse("<+;").unwrap();
+        assert_eq!(tape_underflows(&boolfuck), vec![]);
+        // the procedure isn't analyzed, but the moves after calling it are possible underflows
+        let pbrain = Dialect::Pbrain.parse("(<):<").unwrap();
+        assert_eq!(tape_underflows(&pbrain), vec![Underflow::Possible(pos(4))]);
+    }
+}
@@ -0,0 +1,445 @@
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::tape::{CellType, TapeMode};
+
+use super::{
+    instruction::Operation, ook, substitution::SubstitutionDialect, token::Token, utils::Position,
+    Parser, Program,
+};
+
+/// Languages which can be turned into a brainfuck `Program`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dialect {
+    Brainfuck,
+    Ook,
+    /// Brainfuck with procedures: `(` defines one, `)` ends it and `:` calls it.
+    Pbrain,
+    /// Brainfuck with threads, which are started with `Y`.
+    Brainfork,
+    /// Extended Brainfuck Type I, which adds a storage cell and bitwise operations.
+    /// NOTE: `!` retrieves the storage cell here, so `!TAPE` isn't available
+    ExtendedTypeI,
+    /// Brainfuck on bits, where `+` flips the current bit, `;` prints it and `,` reads one.
+    Boolfuck,
+    /// Brainfuck on bits without any input or output, where `*` flips the current bit.
+    Smallfuck,
+    /// Brainfuck with its operations written as other words.
+    Substitution(SubstitutionDialect),
+}
+
+impl Dialect {
+    /// Guesses the dialect from the extension of the file.
+    pub fn from_path<P: AsRef<Path>>(file: P) -> Option<Self> {
+        match file.as_ref().extension()?.to_str()? {
+            "bf" | "b" => Some(Dialect::Brainfuck),
+            "ook" => Some(Dialect::Ook),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Dialect::Brainfuck => "brainfuck",
+            Dialect::Ook => "ook",
+            Dialect::Pbrain => "pbrain",
+            Dialect::Brainfork => "brainfork",
+            Dialect::ExtendedTypeI => "extended",
+            Dialect::Boolfuck => "boolfuck",
+            Dialect::Smallfuck => "smallfuck",
+            Dialect::Substitution(dialect) => dialect.get_name(),
+        }
+    }
+
+    /// Type of cells the dialect has to be run with, if it's not up to the user.
+    pub fn cell_type(&self) -> Option<CellType> {
+        match self {
+            Dialect::Boolfuck | Dialect::Smallfuck => Some(CellType::Bit),
+            _ => None,
+        }
+    }
+
+    /// Kind of tape the dialect has to be run on, if it's not up to the user.
+    pub fn tape_mode(&self) -> Option<TapeMode> {
+        match self {
+            Dialect::Boolfuck => Some(TapeMode::Infinite),
+            _ => None,
+        }
+    }
+
+    pub fn tokenize(&self, src: &str) -> Result<Vec<Token>, String> {
+        match self {
+            Dialect::Brainfuck => Ok(Parser::tokenize(src)),
+            Dialect::Ook => ook::tokenize(src),
+            Dialect::Pbrain => Ok(Parser::tokenize_with(src, |c| {
+                pbrain_operation(c).or_else(|| Operation::from_symbol(c))
+            })),
+            Dialect::Brainfork => Ok(Parser::tokenize_with(src, |c| match c {
+                'Y' => Some(Operation::Fork),
+                _ => Operation::from_symbol(c),
+            })),
+            Dialect::ExtendedTypeI => Ok(Parser::tokenize_with(src, |c| {
+                extended_type_i_operation(c).or_else(|| Operation::from_symbol(c))
+            })),
+            Dialect::Boolfuck => Ok(Parser::tokenize_with(src, |c| match c {
+                '+' => Some(Operation::CellInc),
+                ';' => Some(Operation::BitRead),
+                ',' => Some(Operation::BitWrite),
+                '<' | '>' | '[' | ']' => Operation::from_symbol(c),
+                _ => None,
+            })),
+            Dialect::Smallfuck => Ok(Parser::tokenize_with(src, |c| match c {
+                '*' => Some(Operation::CellInc),
+                '<' | '>' | '[' | ']' => Operation::from_symbol(c),
+                _ => None,
+            })),
+            Dialect::Substitution(dialect) => Ok(dialect.tokenize(src)),
+        }
+    }
+
+    pub fn parse(&self, src: &str) -> Result<Program, String> {
+        let mut program = Parser::from_tokens(&self.tokenize(src)?)?;
+        program.require_tape(self.cell_type(), self.tape_mode());
+        Ok(program)
+    }
+
+    /// How the operation is written in this dialect, if it has it at all.
+    pub fn write_operation(&self, op: &Operation) -> Option<String> {
+        use Operation::*;
+        let symbol = match (self, op) {
+            (Dialect::Ook, _) => return ook::word(op),
+            (Dialect::Substitution(dialect), _) => return dialect.word(op).map(String::from),
+            (_, TapeLeft | TapeRight | BeginLoop(_) | EndLoop) => op.symbol(),
+            // `!` is an operation there
+            (Dialect::ExtendedTypeI, TapePrint) => return None,
+            (_, TapePrint) => op.symbol(),
+            (Dialect::Boolfuck, CellInc | BitRead | BitWrite) => op.symbol(),
+            (Dialect::Smallfuck, CellInc) => "*",
+            (Dialect::Boolfuck | Dialect::Smallfuck, _) => return None,
+            (_, CellInc | CellDec | CellRead | CellWrite) => op.symbol(),
+            (Dialect::Pbrain, BeginProcedure(_) | EndProcedure | CallProcedure) => op.symbol(),
+            (Dialect::Brainfork, Fork) => op.symbol(),
+            (
+                Dialect::ExtendedTypeI,
+                End | Store | Retrieve | ShiftRight | ShiftLeft | Not | Xor | And | Or,
+            ) => op.symbol(),
+            _ => return None,
+        };
+        Some(symbol.to_string())
+    }
+
+    /// Number of characters of the longest piece of code in this dialect.
+    fn longest_operation(&self) -> usize {
+        match self {
+            // a single Ook without its pair is already an error
+            Dialect::Ook => "Ook.".len(),
+            Dialect::Substitution(dialect) => dialect.longest_word(),
+            _ => "!TAPE".len(),
+        }
+    }
+
+    /// Removes the characters of the comment that would make it code in this dialect.
+    pub fn escape_comment(&self, comment: &str) -> String {
+        let longest = self.longest_operation();
+        let mut kept: Vec<char> = Vec::new();
+        for c in comment.chars() {
+            kept.push(c);
+            // there is no code in what was kept so far,
+            // so it could only appear at its end, with the character that was just added
+            let end = kept[kept.len().saturating_sub(longest)..]
+                .iter()
+                .collect::<String>();
+            let is_code = match self.tokenize(&end) {
+                Ok(tokens) => tokens.iter().any(|t| matches!(t, Token::Op(_, _))),
+                Err(_) => true,
+            };
+            if is_code {
+                kept.pop();
+            }
+        }
+        kept.into_iter().collect::<String>().trim().to_string()
+    }
+
+    /// Writes the program in this dialect together with the comments, keeping them on the same lines.
+    /// `!TAPE` is skipped if the dialect doesn't have it, as it's only for debugging,
+    /// any other operation that the dialect doesn't have is an error,
+    /// and so are the ones that change cells of a different type than this dialect's.
+    pub fn emit(&self, program: &Program, comments: &[Token]) -> Result<String, String> {
+        // words have to be separated, single characters don't
+        let separator = match self {
+            Dialect::Ook | Dialect::Substitution(_) => " ",
+            _ => "",
+        };
+        let same_cells = program.cell_type() == self.cell_type();
+        let mut comments = comments.iter().peekable();
+        let mut out = String::new();
+        let mut line_number = 0;
+        let mut previous_op = false;
+        let mut write = |out: &mut String, line: usize, text: &str, is_op: bool| {
+            while line_number < line {
+                out.push('\n');
+                line_number += 1;
+                previous_op = false;
+            }
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push_str(if previous_op && is_op { separator } else { " " });
+            }
+            out.push_str(text);
+            previous_op = is_op;
+        };
+
+        // the last comments come after all the instructions
+        let instructions = program.instructions().iter().map(Some);
+        for instruction in instructions.chain(iter::once(None)) {
+            let pos = instruction.map(|i| i.get_position());
+            // comments are written before the first instruction that comes after them
+            while let Some(Token::Comment(comment, comment_pos)) =
+                comments.next_if(|c| pos.map_or(true, |pos| before(c.get_position(), pos)))
+            {
+                let comment = self.escape_comment(comment);
+                if !comment.is_empty() {
+                    write(&mut out, comment_pos.line_number, &comment, false);
+                }
+            }
+
+            let instruction = match instruction {
+                Some(instruction) => instruction,
+                None => break,
+            };
+            let (op, pos) = (instruction.get_op(), instruction.get_position());
+            let text = match op {
+                _ if !same_cells && changes_cells(op) => None,
+                Operation::TapePrint => match self.write_operation(op) {
+                    Some(text) => Some(text),
+                    None => continue,
+                },
+                _ => self.write_operation(op),
+            };
+            let text = text.ok_or_else(|| {
+                format!(
+                    "`{}` found at {} can't be written in {}",
+                    op.symbol(),
+                    pos,
+                    self.name()
+                )
+            })?;
+            for _ in 0..instruction.get_n() {
+                write(&mut out, pos.line_number, &text, true);
+            }
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Translates src written in this dialect into the other one,
+    /// checking on the way that it's a valid program.
+    pub fn translate(
+        &self,
+        src: &str,
+        to: &Dialect,
+        keep_comments: bool,
+    ) -> Result<String, String> {
+        let program = self.parse(src)?;
+        let mut comments = Vec::new();
+        if keep_comments {
+            comments = self.tokenize(src)?;
+            comments.retain(|t| matches!(t, Token::Comment(_, _)));
+        }
+        to.emit(&program, &comments)
+    }
+}
+
+fn before(a: &Position, b: &Position) -> bool {
+    (a.line_number, a.char_number) < (b.line_number, b.char_number)
+}
+
+/// Operations which do something else depending on the type of the cells,
+/// e.g. `+` flips a bit, but increments a byte.
+fn changes_cells(op: &Operation) -> bool {
+    matches!(
+        op,
+        Operation::CellInc
+            | Operation::CellDec
+            | Operation::ShiftRight
+            | Operation::ShiftLeft
+            | Operation::Not
+    )
+}
+
+fn pbrain_operation(c: char) -> Option<Operation> {
+    match c {
+        '(' => Some(Operation::BeginProcedure(None)),
+        ')' => Some(Operation::EndProcedure),
+        ':' => Some(Operation::CallProcedure),
+        _ => None,
+    }
+}
+
+fn extended_type_i_operation(c: char) -> Option<Operation> {
+    match c {
+        '@' => Some(Operation::End),
+        '$' => Some(Operation::Store),
+        '!' => Some(Operation::Retrieve),
+        '}' => Some(Operation::ShiftRight),
+        '{' => Some(Operation::ShiftLeft),
+        '~' => Some(Operation::Not),
+        '^' => Some(Operation::Xor),
+        '&' => Some(Operation::And),
+        '|' => Some(Operation::Or),
+        _ => None,
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bf" | "brainfuck" => Ok(Dialect::Brainfuck),
+            "ook" => Ok(Dialect::Ook),
+            "pbrain" => Ok(Dialect::Pbrain),
+            "brainfork" => Ok(Dialect::Brainfork),
+            "extended" | "extended-type-i" => Ok(Dialect::ExtendedTypeI),
+            "boolfuck" => Ok(Dialect::Boolfuck),
+            "smallfuck" => Ok(Dialect::Smallfuck),
+            name => SubstitutionDialect::builtin(name)
+                .map(Dialect::Substitution)
+                .ok_or_else(|| format!("Unknown dialect: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod dialect_tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Dialect::from_path("a/b.bf"), Some(Dialect::Brainfuck));
+        assert_eq!(Dialect::from_path("hello.ook"), Some(Dialect::Ook));
+        assert_eq!(Dialect::from_path("hello.txt"), None);
+        assert_eq!(Dialect::from_path("hello"), None);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("BF".parse(), Ok(Dialect::Brainfuck));
+        assert_eq!("ook".parse(), Ok(Dialect::Ook));
+        assert_eq!("pbrain".parse(), Ok(Dialect::Pbrain));
+        assert_eq!("Brainfork".parse(), Ok(Dialect::Brainfork));
+        assert!(matches!("pikalang".parse(), Ok(Dialect::Substitution(_))));
+        assert!("cobol".parse::<Dialect>().is_err());
+    }
+
+    #[test]
+    fn test_pbrain() {
+        let p = Dialect::Pbrain.parse("+(-[+]):").unwrap();
+        assert_eq!(p.to_brainfuck(), "+(-[+]):");
+        assert_eq!(
+            p.instructions()[1].get_op(),
+            &Operation::BeginProcedure(Some(6))
+        );
+        // in brainfuck these are just comments
+        assert_eq!(
+            Dialect::Brainfuck.parse("+(-):").unwrap().to_brainfuck(),
+            "+-"
+        );
+        assert!(Dialect::Pbrain.parse("(").is_err());
+        assert!(Dialect::Pbrain.parse("([)]").is_err());
+    }
+
+    #[test]
+    fn test_extended_type_i_bang() {
+        let p = Dialect::ExtendedTypeI.parse("$!TAPE").unwrap();
+        assert_eq!(p.to_brainfuck(), "$!");
+        let p = Dialect::Brainfuck.parse("$!TAPE").unwrap();
+        assert_eq!(p.to_brainfuck(), "!TAPE");
+    }
+
+    #[test]
+    fn test_bit_dialects() {
+        let p = Dialect::Boolfuck.parse("+-;.,[>]").unwrap();
+        assert_eq!(p.to_brainfuck(), "+;,[>]");
+        assert_eq!(p.instructions()[1].get_op(), &Operation::BitRead);
+        let p = Dialect::Smallfuck.parse("*+*.[<]").unwrap();
+        assert_eq!(p.to_brainfuck(), "++[<]");
+    }
+
+    #[test]
+    fn test_translate_roundtrip() {
+        // comments without anything that would be code in any of the dialects
+        let src = "+++ x3\n[->+<] mv\n>.";
+        let dialects = ["ook", "pbrain", "extended", "alphuck", "pikalang", "blub"]
+            .iter()
+            .map(|d| d.parse::<Dialect>().unwrap());
+        for dialect in dialects {
+            let translated = Dialect::Brainfuck.translate(src, &dialect, true).unwrap();
+            assert_eq!(
+                dialect.translate(&translated, &Dialect::Brainfuck, true),
+                Ok(src.to_string() + "\n"),
+                "{}",
+                translated
+            );
+        }
+    }
+
+    #[test]
+    fn test_translate_comments() {
+        let ook = Dialect::Brainfuck
+            .translate("+ Ook? ok\n\n-", &Dialect::Ook, true)
+            .unwrap();
+        assert_eq!(ook, "Ook. Ook. Ook ok\n\nOok! Ook!\n");
+        let alphuck = "alphuck".parse::<Dialect>().unwrap();
+        assert_eq!(
+            Dialect::Brainfuck.translate("+ add one", &alphuck, true),
+            Ok("e dd n\n".to_string())
+        );
+        assert_eq!(
+            Dialect::Brainfuck.translate("+ add one", &alphuck, false),
+            Ok("e\n".to_string())
+        );
+        assert!(Dialect::Brainfuck
+            .translate(",", &Dialect::Smallfuck, true)
+            .is_err());
+        assert_eq!(
+            Dialect::Brainfuck.translate("+!TAPE", &Dialect::Ook, true),
+            Ok("Ook. Ook.\n".to_string())
+        );
+        assert_eq!(
+            Dialect::Boolfuck.translate("[<] !T-APE", &Dialect::Brainfuck, true),
+            Ok("[<] !TAP\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_translate_bits() {
+        // Boolfuck's `,` reads a bit and `+` flips it, which brainfuck can't do
+        assert!(Dialect::Boolfuck
+            .translate(",+", &Dialect::Brainfuck, true)
+            .is_err());
+        assert!(Dialect::Brainfuck
+            .translate("+", &Dialect::Boolfuck, true)
+            .is_err());
+        assert_eq!(
+            Dialect::Boolfuck.translate("+[>+]", &Dialect::Smallfuck, true),
+            Ok("*[>*]\n".to_string())
+        );
+        assert_eq!(
+            Dialect::Brainfuck.translate("[<]", &Dialect::Boolfuck, true),
+            Ok("[<]\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_same_program() {
+        let bf = Dialect::Brainfuck.parse("+[->+<]").unwrap();
+        let ook = Dialect::Brainfuck
+            .translate("+[->+<]", &Dialect::Ook, false)
+            .unwrap();
+        let ook = Dialect::Ook.parse(&ook).unwrap();
+        assert_eq!(bf.to_brainfuck(), ook.to_brainfuck());
+    }
+}
@@ -0,0 +1,210 @@
+//! Formatting of brainfuck source code, so that the structure of the program is visible.
+//!
+//! Loops which contain other loops, comments or span many lines are written as blocks,
+//! with `[` ending a line, the body indented by one level and `]` on its own line,
+//! all the other loops stay inline, e.g.:
+//! ```text
+//! ++++++++[
+//!     >++++[
+//!         >++>+++[-]<<-
+//!     ]
+//!     >[-]<-
+//! ]
+//! ```
+//! Code after a block starts on a new line, otherwise code keeps its lines
+//! unless they are too long, comments and single blank lines are kept too.
+
+use super::{instruction::Operation, token::Token, Parser};
+
+pub const DEFAULT_WIDTH: usize = 80;
+const INDENT: &str = "    ";
+
+struct Formatter {
+    width: usize,
+    depth: usize,
+    lines: Vec<String>,
+    /// line that is currently being written, without the indentation
+    line: String,
+    /// whether the last thing written to the line was a comment
+    after_comment: bool,
+    /// whether the line ends a block, so that code can't be added to it
+    after_block: bool,
+}
+
+impl Formatter {
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let line = INDENT.repeat(self.depth) + &self.line;
+            self.lines.push(line);
+            self.line.clear();
+        }
+        self.after_comment = false;
+        self.after_block = false;
+    }
+
+    fn blank_line(&mut self) {
+        self.flush();
+        if self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// Adds a piece of code, which can't be split, to the line,
+    /// starting a new one if it wouldn't fit.
+    fn code(&mut self, code: &str) {
+        let length = INDENT.len() * self.depth + self.line.chars().count() + code.chars().count();
+        if (length > self.width && !self.line.is_empty()) || self.after_block {
+            self.flush();
+        }
+        if self.after_comment {
+            self.line.push(' ');
+        }
+        self.line.push_str(code);
+        self.after_comment = false;
+    }
+
+    fn comment(&mut self, comment: &str) {
+        if !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.line.push_str(comment);
+        self.after_comment = true;
+    }
+}
+
+/// The loop written inline if its body doesn't contain any other loops or comments
+/// and is on the same line as its `[`.
+fn inline_loop(tokens: &[Token], line_number: usize) -> Option<String> {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Op(op, pos) if pos.line_number == line_number => match op {
+                Operation::BeginLoop(_) | Operation::EndLoop => None,
+                op => Some(op.symbol()),
+            },
+            _ => None,
+        })
+        .collect::<Option<String>>()
+        .map(|body| format!("[{}]", body))
+}
+
+/// Formats brainfuck source code, lines will be at most `width` characters long
+/// unless a comment or a piece of code that can't be split doesn't fit.
+pub fn format(src: &str, width: usize) -> Result<String, String> {
+    let tokens = Parser::tokenize(src);
+    let program = Parser::from_tokens(&tokens)?;
+    // positions of the `]`s matching the `[`s, in the same order as the `[`s are in tokens
+    let mut ends =
+        program
+            .instructions()
+            .iter()
+            .filter_map(|instruction| match instruction.get_op() {
+                Operation::BeginLoop(Some(end)) => {
+                    Some(*program.instructions()[*end].get_position())
+                }
+                _ => None,
+            });
+
+    let mut f = Formatter {
+        width,
+        depth: 0,
+        lines: Vec::new(),
+        line: String::new(),
+        after_comment: false,
+        after_block: false,
+    };
+    // ends of loops which are written as blocks
+    let mut blocks = Vec::new();
+    let mut last_line = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let line_number = tokens[i].get_position().line_number;
+        if line_number > last_line + 1 {
+            f.blank_line();
+        } else if line_number > last_line {
+            f.flush();
+        }
+
+        match &tokens[i] {
+            Token::Comment(comment, _) => f.comment(comment),
+            Token::Op(Operation::BeginLoop(_), _) => {
+                let end_pos = ends.next().expect("every `[` was parsed into a loop");
+                let end = i + tokens[i..]
+                    .iter()
+                    .position(|token| token.get_position() == &end_pos)
+                    .expect("`]` of the loop is among the tokens");
+                match inline_loop(&tokens[i + 1..end], line_number) {
+                    Some(code)
+                        if tokens[end].get_position().line_number == line_number
+                            && INDENT.len() * f.depth + code.chars().count() <= width =>
+                    {
+                        f.code(&code);
+                        i = end;
+                    }
+                    _ => {
+                        f.code("[");
+                        f.flush();
+                        f.depth += 1;
+                        blocks.push(end);
+                    }
+                }
+            }
+            Token::Op(Operation::EndLoop, _) if blocks.last() == Some(&i) => {
+                blocks.pop();
+                f.flush();
+                f.depth -= 1;
+                f.code("]");
+                f.after_block = true;
+            }
+            Token::Op(op, _) => f.code(op.symbol()),
+        }
+        last_line = tokens[i].get_position().line_number;
+        i += 1;
+    }
+    f.flush();
+
+    Ok(f.lines.iter().map(|line| line.clone() + "\n").collect())
+}
+
+#[cfg(test)]
+mod format_tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks() {
+        let src = "++++++++[>++++[>++>+++[-]<<-]>[-]<-]";
+        let formatted = "++++++++[\n    >++++[\n        >++>+++[-]<<-\n    ]\n    >[-]<-\n]\n";
+        assert_eq!(format(src, DEFAULT_WIDTH), Ok(formatted.to_string()));
+        assert_eq!(format(formatted, DEFAULT_WIDTH), Ok(formatted.to_string()));
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let src = "  + add one  \n\n\n\n[-] clear\n[\n- loop\n]";
+        let formatted = "+ add one\n\n[-] clear\n[\n    - loop\n]\n";
+        assert_eq!(format(src, DEFAULT_WIDTH), Ok(formatted.to_string()));
+        assert_eq!(format(formatted, DEFAULT_WIDTH), Ok(formatted.to_string()));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let src = "+".repeat(10) + "[" + &"-".repeat(10) + "]";
+        assert_eq!(
+            format(&src, 8),
+            Ok("++++++++\n++[\n    ----\n    ----\n    --\n]\n".to_string())
+        );
+        assert_eq!(format("++[-]", 4), Ok("++\n[-]\n".to_string()));
+    }
+
+    #[test]
+    fn test_same_program() {
+        let src = "+[>,. echo\n[<]>]";
+        let formatted = format(src, DEFAULT_WIDTH).unwrap();
+        assert_eq!(
+            Parser::parse(&formatted).unwrap().to_brainfuck(),
+            Parser::parse(src).unwrap().to_brainfuck()
+        );
+        assert_eq!(format("+[]", DEFAULT_WIDTH), Ok("+[]\n".to_string()));
+        assert!(format("[", DEFAULT_WIDTH).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use super::utils::{Address, Position};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Operation {
     TapeLeft,
     TapeRight,
@@ -13,12 +13,45 @@ pub enum Operation {
     /// Contains the address of the matching EndLoop or None if it wasn't set yet.
     BeginLoop(Option<Address>),
     EndLoop,
+    /// Defines a procedure numbered with the value of the current cell (pbrain),
+    /// contains the address of the matching EndProcedure or None if it wasn't set yet.
+    BeginProcedure(Option<Address>),
+    /// Returns from the procedure to the instruction after its call.
+    EndProcedure,
+    /// Calls the procedure numbered with the value of the current cell.
+    CallProcedure,
+    /// Starts a new thread (brainfork), see `Interpreter` for the details.
+    Fork,
+    /// Ends the whole program (Extended Type I).
+    End,
+    /// Copies the current cell to the storage cell.
+    Store,
+    /// Copies the storage cell to the current cell.
+    Retrieve,
+    /// Shifts the bits of the current cell once to the right.
+    ShiftRight,
+    /// Shifts the bits of the current cell once to the left.
+    ShiftLeft,
+    /// Bitwise NOT of the current cell.
+    Not,
+    /// Bitwise XOR of the current cell and the storage cell, stored in the current cell.
+    Xor,
+    /// Same as Xor but AND.
+    And,
+    /// Same as Xor but OR.
+    Or,
+    /// Prints the bit in the current cell (Boolfuck), once there are 8 of them
+    /// they are written out as a byte, starting from the least significant bit.
+    BitRead,
+    /// Reads the next bit of the input into the current cell,
+    /// bytes are also read starting from the least significant bit.
+    BitWrite,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Instruction {
     /// number of times this operation should be repeated
-    /// NOTE: BeginLoop and EndLoop will always have this set to 1
+    /// NOTE: only groupable operations can have this set to something other than 1
     n: usize,
     /// type of operation that will be performed
     op: Operation,
@@ -27,6 +60,66 @@ pub struct Instruction {
     pos: Position,
 }
 
+impl Operation {
+    /// Operations which can be repeated many times in a single instruction.
+    pub fn is_groupable(&self) -> bool {
+        matches!(
+            self,
+            Operation::CellDec | Operation::CellInc | Operation::TapeLeft | Operation::TapeRight
+        )
+    }
+
+    /// Whether both are the same operation, regardless of the addresses they contain.
+    pub fn same_kind(&self, other: &Operation) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Operation written as this single character in brainfuck.
+    pub fn from_symbol(c: char) -> Option<Self> {
+        match c {
+            '<' => Some(Operation::TapeLeft),
+            '>' => Some(Operation::TapeRight),
+            '+' => Some(Operation::CellInc),
+            '-' => Some(Operation::CellDec),
+            '.' => Some(Operation::CellRead),
+            ',' => Some(Operation::CellWrite),
+            '[' => Some(Operation::BeginLoop(None)),
+            ']' => Some(Operation::EndLoop),
+            _ => None,
+        }
+    }
+
+    /// How this operation is written in brainfuck.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operation::TapeLeft => "<",
+            Operation::TapeRight => ">",
+            Operation::TapePrint => "!TAPE",
+            Operation::CellInc => "+",
+            Operation::CellDec => "-",
+            Operation::CellRead => ".",
+            Operation::CellWrite => ",",
+            Operation::BeginLoop(_) => "[",
+            Operation::EndLoop => "]",
+            Operation::BeginProcedure(_) => "(",
+            Operation::EndProcedure => ")",
+            Operation::CallProcedure => ":",
+            Operation::Fork => "Y",
+            Operation::End => "@",
+            Operation::Store => "$",
+            Operation::Retrieve => "!",
+            Operation::ShiftRight => "}",
+            Operation::ShiftLeft => "{",
+            Operation::Not => "~",
+            Operation::Xor => "^",
+            Operation::And => "&",
+            Operation::Or => "|",
+            Operation::BitRead => ";",
+            Operation::BitWrite => ",",
+        }
+    }
+}
+
 impl Instruction {
     pub fn new(n: usize, op: Operation, pos: Position) -> Self {
         Self { n, op, pos }
@@ -40,17 +133,25 @@ impl Instruction {
     pub fn get_position(&self) -> &Position {
         &self.pos
     }
+    /// Makes this instruction do its operation one more time.
+    pub fn repeat(&mut self) {
+        self.n += 1;
+    }
+    /// Sets the address of the matching EndLoop or EndProcedure.
     pub fn set_end_of_loop_address(&mut self, addr: Address) {
         match self.op {
             Operation::BeginLoop(None) => {
                 self.op = Operation::BeginLoop(Some(addr));
             }
-            Operation::BeginLoop(_) => {
+            Operation::BeginProcedure(None) => {
+                self.op = Operation::BeginProcedure(Some(addr));
+            }
+            Operation::BeginLoop(_) | Operation::BeginProcedure(_) => {
                 panic!("set_end_of_loop_address was called more than once!")
             }
             _ => {
                 panic!(
-                    "set_end_of_loop_address called on a non-BeginLoop instruction! instruction: {:?}",
+                    "set_end_of_loop_address called on an instruction which doesn't begin a block! instruction: {:?}",
                     self
                 )
             }
@@ -0,0 +1,185 @@
+//! Warnings about common mistakes, found without running the program.
+
+use core::fmt::Display;
+
+use super::{
+    analysis::{tape_underflows, Underflow},
+    instruction::Operation,
+    utils::Position,
+    Program,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    /// Loop starts right after another one or at the beginning of the program,
+    /// where the cell is always 0, so it can never be entered.
+    DeadLoop(Position),
+    /// Loop which changes cells, but also moves the pointer by this many cells
+    /// every iteration, while usually such loops end where they started.
+    UnbalancedLoop(isize, Position),
+    /// `[-]` on a cell which is already 0.
+    ClearingZero(Position),
+    /// Going to the left of the first cell whenever this move is executed.
+    TapeUnderflow(Position),
+    /// Going to the left of the first cell depending on the values of cells.
+    PossibleTapeUnderflow(Position),
+    /// `[]` which never ends unless the cell is 0.
+    InfiniteLoop(Position),
+}
+
+impl Warning {
+    pub fn position(&self) -> &Position {
+        match self {
+            Warning::DeadLoop(pos)
+            | Warning::UnbalancedLoop(_, pos)
+            | Warning::ClearingZero(pos)
+            | Warning::TapeUnderflow(pos)
+            | Warning::PossibleTapeUnderflow(pos)
+            | Warning::InfiniteLoop(pos) => pos,
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Warning::DeadLoop(_) => {
+                "Loop can never be entered, as the cell is always 0 here".to_string()
+            }
+            Warning::UnbalancedLoop(n, _) => {
+                format!("Loop moves the pointer by {} cell(s) every iteration", n)
+            }
+            Warning::ClearingZero(_) => {
+                "Cell is always 0 here, so clearing it does nothing".to_string()
+            }
+            Warning::TapeUnderflow(_) => {
+                "Pointer always goes to the left of the first cell".to_string()
+            }
+            Warning::PossibleTapeUnderflow(_) => {
+                "Pointer may go to the left of the first cell".to_string()
+            }
+            Warning::InfiniteLoop(_) => "Empty loop never ends unless the cell is 0".to_string(),
+        };
+        f.write_fmt(format_args!("{} at {}", msg, self.position()))
+    }
+}
+
+/// Checks the program for common mistakes, warnings are sorted by their positions.
+/// Moves which only may go to the left of the first cell are reported
+/// if `possible_underflows` is set, as they are expected in loops like `[<]`.
+pub fn lint(program: &Program, possible_underflows: bool) -> Vec<Warning> {
+    let instructions = program.instructions();
+    let mut warnings = Vec::new();
+
+    for underflow in tape_underflows(program) {
+        match underflow {
+            Underflow::Guaranteed(pos) => warnings.push(Warning::TapeUnderflow(pos)),
+            Underflow::Possible(pos) if possible_underflows => {
+                warnings.push(Warning::PossibleTapeUnderflow(pos))
+            }
+            Underflow::Possible(_) => {}
+        }
+    }
+
+    for (addr, i) in instructions.iter().enumerate() {
+        let end = match i.get_op() {
+            Operation::BeginLoop(Some(end)) => *end,
+            _ => continue,
+        };
+        let pos = *i.get_position();
+        let body = &instructions[addr + 1..end];
+        let is_clear = matches!(
+            body,
+            [b] if matches!(b.get_op(), Operation::CellDec | Operation::CellInc) && b.get_n() == 1
+        );
+
+        // the cell is 0 at the start of the program and right after a loop
+        if addr == 0 || instructions[addr - 1].get_op() == &Operation::EndLoop {
+            warnings.push(if is_clear {
+                Warning::ClearingZero(pos)
+            } else {
+                Warning::DeadLoop(pos)
+            });
+            continue;
+        }
+        if body.is_empty() {
+            warnings.push(Warning::InfiniteLoop(pos));
+            continue;
+        }
+
+        // only innermost loops with nothing but the basic operations can be checked
+        let mut movement: isize = 0;
+        let mut changes_cells = false;
+        let mut checkable = true;
+        for b in body {
+            match b.get_op() {
+                Operation::TapeRight => movement += b.get_n() as isize,
+                Operation::TapeLeft => movement -= b.get_n() as isize,
+                Operation::CellInc | Operation::CellDec | Operation::CellWrite => {
+                    changes_cells = true
+                }
+                Operation::CellRead | Operation::TapePrint => {}
+                _ => checkable = false,
+            }
+        }
+        // loops like `[>]` are meant to move, they look for the first 0
+        if checkable && changes_cells && movement != 0 {
+            warnings.push(Warning::UnbalancedLoop(movement, pos));
+        }
+    }
+
+    warnings.sort_by(|a, b| {
+        a.position()
+            .partial_cmp(b.position())
+            .expect("positions can always be compared")
+    });
+    warnings
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn lint_src(src: &str) -> Vec<Warning> {
+        lint(&Parser::parse(src).unwrap(), false)
+    }
+
+    fn pos(char_number: usize) -> Position {
+        Position {
+            line_number: 0,
+            char_number,
+        }
+    }
+
+    #[test]
+    fn test_clean_program() {
+        assert_eq!(lint_src("++++++[>++++++++<-]>+.[>]<[-]"), vec![]);
+    }
+
+    #[test]
+    fn test_loops() {
+        assert_eq!(lint_src("[comment]"), vec![Warning::DeadLoop(pos(0))]);
+        assert_eq!(lint_src("[-]"), vec![Warning::ClearingZero(pos(0))]);
+        assert_eq!(
+            lint_src("+[-][.]+[]"),
+            vec![Warning::DeadLoop(pos(4)), Warning::InfiniteLoop(pos(8))]
+        );
+        assert_eq!(
+            lint_src("+[>+>+<-]"),
+            vec![Warning::UnbalancedLoop(1, pos(1))]
+        );
+    }
+
+    #[test]
+    fn test_tape_underflow() {
+        assert_eq!(lint_src("+>.<<"), vec![Warning::TapeUnderflow(pos(3))]);
+        assert_eq!(lint_src("+[>+<-]<"), vec![Warning::TapeUnderflow(pos(7))]);
+        let program = Parser::parse(">+[<]").unwrap();
+        assert_eq!(lint(&program, false), vec![]);
+        assert_eq!(
+            lint(&program, true),
+            vec![Warning::PossibleTapeUnderflow(pos(3))]
+        );
+    }
+}
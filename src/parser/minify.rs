@@ -0,0 +1,95 @@
+//! Shortest equivalent brainfuck, without any comments or operations which cancel out.
+
+use std::iter;
+
+use super::Parser;
+
+/// Removes pairs of operations that undo each other, like `+-` or `<>`.
+fn cancel<'a>(ops: &[&'a str]) -> Vec<&'a str> {
+    let mut out: Vec<&str> = Vec::with_capacity(ops.len());
+    for &op in ops {
+        match (out.last(), op) {
+            (Some(&"+"), "-") | (Some(&"-"), "+") | (Some(&">"), "<") | (Some(&"<"), ">") => {
+                out.pop();
+            }
+            _ => out.push(op),
+        }
+    }
+    out
+}
+
+/// Removes loops which can never be entered, because they start right after
+/// the end of another loop or at the very beginning of the program, where the cell is 0.
+fn remove_dead_loops<'a>(ops: &[&'a str]) -> Vec<&'a str> {
+    let mut out: Vec<&str> = Vec::with_capacity(ops.len());
+    let mut i = 0;
+    while i < ops.len() {
+        if ops[i] == "[" && matches!(out.last(), None | Some(&"]")) {
+            // skip the whole loop
+            let mut depth = 0;
+            loop {
+                match ops[i] {
+                    "[" => depth += 1,
+                    "]" => depth -= 1,
+                    _ => {}
+                }
+                i += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            continue;
+        }
+        out.push(ops[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Writes the brainfuck program in as few characters as possible.
+pub fn minify(src: &str) -> Result<String, String> {
+    let program = Parser::parse(src)?;
+    let mut ops = program
+        .instructions()
+        .iter()
+        .flat_map(|i| iter::repeat(i.get_op().symbol()).take(i.get_n()))
+        .collect::<Vec<_>>();
+
+    // removing a loop may put together operations which cancel out and the other way around
+    loop {
+        let minified = remove_dead_loops(&cancel(&ops));
+        if minified.len() == ops.len() {
+            break;
+        }
+        ops = minified;
+    }
+    Ok(ops.concat())
+}
+
+#[cfg(test)]
+mod minify_tests {
+    use super::*;
+
+    #[test]
+    fn test_cancelling() {
+        assert_eq!(minify("+ - + comment"), Ok("+".to_string()));
+        assert_eq!(minify(">+<>-<"), Ok("".to_string()));
+        assert_eq!(minify("+>><<<-"), Ok("+<-".to_string()));
+    }
+
+    #[test]
+    fn test_dead_loops() {
+        assert_eq!(minify("[comment, with commas.]+"), Ok("+".to_string()));
+        assert_eq!(minify("+[-][->+<][[]]>."), Ok("+[-]>.".to_string()));
+        // the loop is only dead once what's before it cancels out
+        assert_eq!(minify("+-[.]<>[.]"), Ok("".to_string()));
+        assert_eq!(minify("+[-]+-[.]"), Ok("+[-]".to_string()));
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let src = "++++[>++<-]>.";
+        assert_eq!(minify(src), Ok(src.to_string()));
+        assert!(minify("[").is_err());
+    }
+}
@@ -1,26 +1,242 @@
+pub mod analysis;
+pub mod dialect;
+pub mod format;
 pub mod instruction;
+pub mod lint;
+pub mod minify;
+pub mod ook;
+pub mod substitution;
+pub mod token;
 pub mod utils;
+use std::collections::HashMap;
+
+use crate::interpreter::folding::Prefix;
 use crate::parser::instruction::{Instruction, Operation};
+use crate::tape::{CellType, TapeMode};
+use token::Token;
 use utils::Position;
 
 use self::utils::{Address, BracketCountMismatch};
 
+/// Where a program is in its execution, every brainfork thread has its own.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Context {
+    pub pc: Address,
+    pub stack: Vec<Address>,
+    pub calls: Vec<Address>,
+}
+
 #[derive(Debug, PartialEq, Default)]
 pub struct Program {
     instructions: Vec<Instruction>,
     stack: Vec<usize>,
     pc: Address,
+    /// numbers of the defined procedures and addresses of their BeginProcedures
+    procedures: HashMap<usize, Address>,
+    /// addresses of the CallProcedures whose procedures are currently being executed
+    calls: Vec<Address>,
+    /// type of cells and tape the program has to be run on, if its dialect requires them
+    cell_type: Option<CellType>,
+    tape_mode: Option<TapeMode>,
+    /// state after the part of the program which doesn't read any input, see `set_prefix`
+    prefix: Option<Prefix>,
 }
 
 impl Program {
     pub fn reset(&mut self) {
         self.pc = 0;
+        self.stack.clear();
+        self.procedures.clear();
+        self.calls.clear();
+    }
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+    /// Writes the program with the brainfuck symbols of its operations (see `Operation::symbol`),
+    /// without any comments or whitespace. Operations of other dialects keep their own symbols,
+    /// e.g. Boolfuck's `;`, so it's only brainfuck if the program doesn't have any of them,
+    /// `Dialect::translate` checks that.
+    pub fn to_brainfuck(&self) -> String {
+        self.instructions
+            .iter()
+            .map(|i| i.get_op().symbol().repeat(i.get_n()))
+            .collect()
+    }
+    /// Hash of all the instructions, which stays the same between runs and versions
+    /// of Rust (unlike `DefaultHasher`), so that it can be saved, e.g. in snapshots.
+    pub fn fingerprint(&self) -> u64 {
+        // 64-bit FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for instruction in &self.instructions {
+            let op = format!("{:?}", instruction.get_op());
+            for byte in op.bytes().chain(instruction.get_n().to_le_bytes()) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+    pub fn get_pc(&self) -> Address {
+        self.pc
+    }
+    /// Addresses of BeginLoops of all the loops that are currently being executed.
+    pub fn get_stack(&self) -> &[Address] {
+        &self.stack
+    }
+    /// Puts the program in the middle of its execution,
+    /// will return an Error if any of the addresses are outside of the program
+    /// or the stack doesn't hold exactly the loops the program is in, see `check_context`.
+    /// NOTE: procedures have to be set first, as their calls are a part of that
+    pub fn set_state(&mut self, pc: Address, stack: Vec<Address>) -> Result<(), String> {
+        let context = Context {
+            pc,
+            stack,
+            calls: self.calls.clone(),
+        };
+        self.check_context(&context)?;
+        self.pc = context.pc;
+        self.stack = context.stack;
+        Ok(())
+    }
+    /// Makes sure that the program can continue from the context:
+    /// pc has to be inside of the program, calls have to point at CallProcedures
+    /// and the stack has to hold the loops enclosing each of the calls, up to the procedure
+    /// they are in, followed by the ones enclosing pc.
+    pub fn check_context(&self, context: &Context) -> Result<(), String> {
+        if context.pc > self.instructions.len() {
+            return Err(format!(
+                "Program counter {} is outside of the program of length {}",
+                context.pc,
+                self.instructions.len()
+            ));
+        }
+        if let Some(addr) = context.calls.iter().find(|&&addr| {
+            self.instructions.get(addr).map(|i| i.get_op()) != Some(&Operation::CallProcedure)
+        }) {
+            return Err(format!("There is no procedure call at address {}", addr));
+        }
+        let loops = context
+            .calls
+            .iter()
+            .chain(std::iter::once(&context.pc))
+            .flat_map(|&addr| self.enclosing_loops(addr))
+            .collect::<Vec<_>>();
+        if context.stack != loops {
+            return Err(format!(
+                "Stack {:?} doesn't match the loops {:?} the program is in at address {}",
+                context.stack, loops, context.pc
+            ));
+        }
+        Ok(())
+    }
+    /// Addresses of BeginLoops of the loops which contain the instruction at addr,
+    /// from the outermost one, but only inside of the procedure it's in.
+    fn enclosing_loops(&self, addr: Address) -> Vec<Address> {
+        let mut loops = Vec::new();
+        for (begin, instruction) in self.instructions[..addr].iter().enumerate().rev() {
+            match instruction.get_op() {
+                Operation::BeginLoop(Some(end)) if *end >= addr => loops.push(begin),
+                Operation::BeginProcedure(Some(end)) if *end >= addr => break,
+                _ => {}
+            }
+        }
+        loops.reverse();
+        loops
+    }
+    /// Numbers of the procedures defined so far and addresses of their BeginProcedures.
+    pub fn get_procedures(&self) -> &HashMap<usize, Address> {
+        &self.procedures
+    }
+    /// Addresses of CallProcedures of all the procedures that are currently being executed.
+    pub fn get_calls(&self) -> &[Address] {
+        &self.calls
+    }
+    /// Same as `set_state` but for procedures, will return an Error
+    /// if any of the addresses don't point to the right kind of instruction.
+    pub fn set_procedures(
+        &mut self,
+        procedures: HashMap<usize, Address>,
+        calls: Vec<Address>,
+    ) -> Result<(), String> {
+        if let Some(addr) = procedures.values().find(|&&addr| {
+            !matches!(
+                self.instructions.get(addr).map(|i| i.get_op()),
+                Some(Operation::BeginProcedure(_))
+            )
+        }) {
+            return Err(format!(
+                "There is no procedure starting at address {}",
+                addr
+            ));
+        }
+        if let Some(addr) = calls.iter().find(|&&addr| {
+            self.instructions.get(addr).map(|i| i.get_op()) != Some(&Operation::CallProcedure)
+        }) {
+            return Err(format!("There is no procedure call at address {}", addr));
+        }
+        self.procedures = procedures;
+        self.calls = calls;
+        Ok(())
+    }
+    pub fn cell_type(&self) -> Option<CellType> {
+        self.cell_type
+    }
+    pub fn tape_mode(&self) -> Option<TapeMode> {
+        self.tape_mode
+    }
+    /// Makes the interpreter run the program on these cells and tape
+    /// instead of the ones from its options.
+    pub fn require_tape(&mut self, cell_type: Option<CellType>, tape_mode: Option<TapeMode>) {
+        self.cell_type = cell_type;
+        self.tape_mode = tape_mode;
+    }
+    pub fn prefix(&self) -> Option<&Prefix> {
+        self.prefix.as_ref()
+    }
+    /// Keeps the evaluated prefix of the program, so that whenever the interpreter starts
+    /// running it on a blank tape it continues from there instead.
+    pub fn set_prefix(&mut self, prefix: Option<Prefix>) {
+        self.prefix = prefix;
+    }
+    /// Current execution state, which can be put back with `swap_context`.
+    pub fn get_context(&self) -> Context {
+        Context {
+            pc: self.pc,
+            stack: self.stack.clone(),
+            calls: self.calls.clone(),
+        }
+    }
+    /// Continues from the given context, leaving the current one in its place.
+    pub fn swap_context(&mut self, context: &mut Context) {
+        std::mem::swap(&mut self.pc, &mut context.pc);
+        std::mem::swap(&mut self.stack, &mut context.stack);
+        std::mem::swap(&mut self.calls, &mut context.calls);
+    }
+    /// Position of the instruction the program has stopped on,
+    /// or of the last one if it has already finished.
+    pub fn current_position(&self) -> Position {
+        self.instructions
+            .get(self.pc)
+            .or_else(|| self.instructions.last())
+            .map(|i| *i.get_position())
+            .unwrap_or(Position {
+                line_number: 0,
+                char_number: 0,
+            })
     }
     pub fn fetch_instruction(&mut self) -> Option<&Instruction> {
         self.instructions.get(self.pc)
     }
     pub fn inc_pc(&mut self) {
-        self.pc += 1;
+        if self.pc < self.instructions.len() {
+            self.pc += 1;
+        }
+    }
+    /// Moves right past the last instruction, so that the program finishes.
+    pub fn end(&mut self) {
+        self.pc = self.instructions.len();
+        self.stack.clear();
+        self.calls.clear();
     }
     pub fn jump(&mut self, addr: usize) {
         if addr < self.instructions.len() {
@@ -45,6 +261,38 @@ impl Program {
             self.stack.push(self.pc);
         }
     }
+    /// Remembers where the procedure starts and skips its body.
+    pub fn define_procedure(&mut self, number: usize) {
+        match self.instructions.get(self.pc).map(|i| i.get_op()) {
+            Some(Operation::BeginProcedure(Some(end_address))) => {
+                let end_address = *end_address;
+                self.procedures.insert(number, self.pc);
+                self.jump(end_address);
+            }
+            i => panic!(
+                "define_procedure called on a wrong instruction: {:?}, address: {}",
+                i, self.pc
+            ),
+        }
+    }
+    /// Jumps into the procedure, so that it starts with the next instruction,
+    /// will return an Error if there is no such procedure.
+    pub fn call_procedure(&mut self, number: usize) -> Result<(), String> {
+        match self.procedures.get(&number) {
+            Some(&start_address) => {
+                self.calls.push(self.pc);
+                self.jump(start_address);
+                Ok(())
+            }
+            None => Err(format!("Procedure {} is not defined", number)),
+        }
+    }
+    /// Goes back to the call of the procedure that has just finished.
+    pub fn end_procedure(&mut self) {
+        if let Some(call_address) = self.calls.pop() {
+            self.jump(call_address);
+        }
+    }
     pub fn end_loop(&mut self, cell_value: usize) {
         if cell_value == 0 {
             // exit the loop
@@ -64,14 +312,28 @@ impl Parser {
     /// Produces a ready-to-run program from src.
     /// In case of an error will return a String describing it.
     pub fn parse(src: &str) -> Result<Program, String> {
-        Self::check_brackets(src).map_err(|e| e.to_string())?;
+        Self::from_tokens(&Self::tokenize(src))
+    }
 
-        let mut program = Program::default();
+    /// Splits brainfuck source code into operations and comments.
+    pub fn tokenize(src: &str) -> Vec<Token> {
+        Self::tokenize_with(src, Operation::from_symbol)
+    }
+
+    /// Splits source code of a brainfuck-like language, where every operation
+    /// is a single character, into operations and comments.
+    /// `operation` gives the operation of a character, `!TAPE` is also recognized
+    /// unless `!` is an operation.
+    pub fn tokenize_with<F: Fn(char) -> Option<Operation>>(src: &str, operation: F) -> Vec<Token> {
+        let mut tokens = Vec::new();
         let chars = src.chars().collect::<Vec<_>>();
         let mut pos = Position {
             line_number: 0,
             char_number: 0,
         };
+        // comment that is currently being read and where it started
+        let mut comment = String::new();
+        let mut comment_pos = pos;
 
         let mut i = 0;
         while i < chars.len() {
@@ -79,69 +341,87 @@ impl Parser {
             // vim just prints '\r', emacs considers them newlines,
             // on windows '\r\n' is usually used at the end of lines,
             // so let's consider standalone '\r' as EOL and skip '\n' that follows
-            if c == '\r' {
-                if i + 1 < chars.len() && chars[i + 1] == '\n' {
+            if c == '\r' || c == '\n' {
+                if c == '\r' && i + 1 < chars.len() && chars[i + 1] == '\n' {
                     i += 1;
                 }
-                pos.line_number += 1;
-                pos.char_number = 0;
-                i += 1;
-                continue;
-            } else if c == '\n' {
+                Self::push_comment(&mut tokens, &mut comment, comment_pos);
                 pos.line_number += 1;
                 pos.char_number = 0;
                 i += 1;
                 continue;
             }
 
-            let op = match c {
-                '-' => Operation::CellDec,
-                '+' => Operation::CellInc,
-                '<' => Operation::TapeLeft,
-                '>' => Operation::TapeRight,
-                ',' => Operation::CellWrite,
-                '.' => Operation::CellRead,
-                '[' => Operation::BeginLoop(None),
-                ']' => Operation::EndLoop,
-                '!' => {
-                    if i + 4 < chars.len() && chars[i + 1..i + 5] == ['T', 'A', 'P', 'E'] {
-                        i += 4;
-                        pos.char_number += 4;
-                        Operation::TapePrint
-                    } else {
-                        pos.char_number += 1;
-                        i += 1;
-                        continue;
-                    }
+            let op = operation(c).or_else(|| match c {
+                '!' if i + 4 < chars.len() && chars[i + 1..i + 5] == ['T', 'A', 'P', 'E'] => {
+                    Some(Operation::TapePrint)
                 }
-                _ => {
+                _ => None,
+            });
+            let op = match op {
+                Some(op) => op,
+                None => {
+                    if comment.is_empty() {
+                        if c.is_whitespace() {
+                            pos.char_number += 1;
+                            i += 1;
+                            continue;
+                        }
+                        comment_pos = pos;
+                    }
+                    comment.push(c);
                     pos.char_number += 1;
                     i += 1;
                     continue;
                 }
             };
 
-            // if a character is one of the groupable ones, find out how many times
-            // it is repeating, so that we can then combine, the same operation done multiple times,
-            // into single instruction
-            let mut n = 1;
-            if op == Operation::CellDec
-                || op == Operation::CellInc
-                || op == Operation::TapeLeft
-                || op == Operation::TapeRight
-            {
-                let mut j = i + 1;
-                while j < chars.len() && chars[j] == c {
-                    n += 1;
-                    j += 1;
+            Self::push_comment(&mut tokens, &mut comment, comment_pos);
+            let len = if op == Operation::TapePrint { 5 } else { 1 };
+            tokens.push(Token::Op(op, pos));
+            pos.char_number += len;
+            i += len;
+        }
+        Self::push_comment(&mut tokens, &mut comment, comment_pos);
+
+        tokens
+    }
+
+    pub(crate) fn push_comment(tokens: &mut Vec<Token>, comment: &mut String, pos: Position) {
+        let text = comment.trim_end();
+        if !text.is_empty() {
+            tokens.push(Token::Comment(text.to_string(), pos));
+        }
+        comment.clear();
+    }
+
+    /// Produces a ready-to-run program from tokens of any dialect.
+    /// Repeated operations written directly one after another are grouped together
+    /// into single instructions, this only happens for operations one character long.
+    pub fn from_tokens(tokens: &[Token]) -> Result<Program, String> {
+        Self::check_tokens_brackets(tokens).map_err(|e| e.to_string())?;
+
+        let mut program = Program::default();
+        for token in tokens {
+            let (op, pos) = match token {
+                Token::Op(op, pos) => (op, pos),
+                Token::Comment(_, _) => continue,
+            };
+
+            // if an operation is one of the groupable ones, and it directly follows
+            // the same operation, then we can combine them into a single instruction
+            match program.instructions.last_mut() {
+                Some(last)
+                    if op.is_groupable()
+                        && last.get_op() == op
+                        && last.get_position().line_number == pos.line_number
+                        && last.get_position().char_number + last.get_n() == pos.char_number =>
+                {
+                    last.repeat()
                 }
-                program.instructions.push(Instruction::new(n, op, pos));
-                pos.char_number += j - i;
-                i = j;
-            } else {
-                program.instructions.push(Instruction::new(n, op, pos));
-                pos.char_number += 1;
-                i += 1
+                _ => program
+                    .instructions
+                    .push(Instruction::new(1, op.clone(), *pos)),
             }
         }
 
@@ -153,22 +433,50 @@ impl Parser {
         Ok(program)
     }
 
+    /// Splits src at the first `!` that isn't a part of `!TAPE`,
+    /// into the program and the input which should be fed to it.
+    /// If there is no such `!` the whole src is the program and the input is empty.
+    pub fn split_input(src: &str) -> (&str, &str) {
+        match src
+            .match_indices('!')
+            .find(|(i, _)| !src[i + 1..].starts_with("TAPE"))
+        {
+            Some((i, _)) => (&src[..i], &src[i + 1..]),
+            None => (src, ""),
+        }
+    }
+
     pub fn check_brackets(src: &str) -> Result<(), BracketCountMismatch> {
-        let mut opening_brackets: Vec<Position> = Vec::new();
-        for (i, line) in src.lines().enumerate() {
-            for (j, char) in line.chars().enumerate() {
-                let pos = Position {
-                    line_number: i,
-                    char_number: j,
-                };
-                if char == '[' {
-                    opening_brackets.push(pos);
-                } else if char == ']' && opening_brackets.pop().is_none() {
-                    return Err(BracketCountMismatch::MoreClosing(pos));
+        Self::check_tokens_brackets(&Self::tokenize(src))
+    }
+
+    /// Checks that both loops and procedures are closed and nested properly.
+    pub fn check_tokens_brackets(tokens: &[Token]) -> Result<(), BracketCountMismatch> {
+        // whether the bracket opens a procedure and where it is
+        let mut opening_brackets: Vec<(bool, Position)> = Vec::new();
+        for token in tokens {
+            let (is_procedure, pos) = match token {
+                Token::Op(Operation::BeginLoop(_), pos) => {
+                    opening_brackets.push((false, *pos));
+                    continue;
+                }
+                Token::Op(Operation::BeginProcedure(_), pos) => {
+                    opening_brackets.push((true, *pos));
+                    continue;
                 }
+                Token::Op(Operation::EndLoop, pos) => (false, pos),
+                Token::Op(Operation::EndProcedure, pos) => (true, pos),
+                _ => continue,
+            };
+            match opening_brackets.pop() {
+                None => return Err(BracketCountMismatch::MoreClosing(*pos)),
+                Some((opens_procedure, opening)) if opens_procedure != is_procedure => {
+                    return Err(BracketCountMismatch::Interleaved(opening, *pos))
+                }
+                Some(_) => {}
             }
         }
-        if let Some(pos) = opening_brackets.pop() {
+        if let Some((_, pos)) = opening_brackets.pop() {
             Err(BracketCountMismatch::MoreOpening(pos))
         } else {
             Ok(())
@@ -177,6 +485,7 @@ impl Parser {
 
     /// The point of this function is to fill in all BeginLoop's addresses which store
     /// the position of their matching EndLoop, so that at runtime skipping a loop is trivial!
+    /// The same is done for BeginProcedures, whose bodies are skipped when they are defined.
     /// This is a seperate function from `parse` to take away complexity from it having to
     /// also keep track of positions of all the loops and can just focus on producing
     /// an AST, which is only then fed into this function.
@@ -184,8 +493,8 @@ impl Parser {
         let mut stack: Vec<usize> = Vec::new();
         for i in 0..instructions.len() {
             match instructions[i].get_op() {
-                Operation::BeginLoop(None) => stack.push(i),
-                Operation::EndLoop => match stack.pop() {
+                Operation::BeginLoop(None) | Operation::BeginProcedure(None) => stack.push(i),
+                Operation::EndLoop | Operation::EndProcedure => match stack.pop() {
                     Some(beg) => match instructions.get_mut(beg) {
                         Some(begin_loop_instruction) => {
                             begin_loop_instruction.set_end_of_loop_address(i)
@@ -260,6 +569,70 @@ mod parser_tests {
         );
     }
 
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            Parser::tokenize("+ add one\n  !TAPE ! -"),
+            vec![
+                Token::Op(
+                    Operation::CellInc,
+                    Position {
+                        line_number: 0,
+                        char_number: 0
+                    }
+                ),
+                Token::Comment(
+                    "add one".to_string(),
+                    Position {
+                        line_number: 0,
+                        char_number: 2
+                    }
+                ),
+                Token::Op(
+                    Operation::TapePrint,
+                    Position {
+                        line_number: 1,
+                        char_number: 2
+                    }
+                ),
+                Token::Comment(
+                    "!".to_string(),
+                    Position {
+                        line_number: 1,
+                        char_number: 8
+                    }
+                ),
+                Token::Op(
+                    Operation::CellDec,
+                    Position {
+                        line_number: 1,
+                        char_number: 10
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grouping_only_adjacent_operations() {
+        let p = Parser::parse("++ +\n+a+>>").unwrap();
+        assert_eq!(p.instructions.len(), 5);
+        assert_eq!(p.instructions[0].get_n(), 2);
+        assert_eq!(p.instructions[1].get_n(), 1);
+        assert_eq!(p.instructions[2].get_n(), 1);
+        assert_eq!(p.instructions[3].get_n(), 1);
+        assert_eq!(p.instructions[4].get_n(), 2);
+    }
+
+    #[test]
+    fn test_split_input() {
+        assert_eq!(Parser::split_input(",[.,]!abc"), (",[.,]", "abc"));
+        assert_eq!(Parser::split_input(",[.,]"), (",[.,]", ""));
+        assert_eq!(Parser::split_input(",!TAPE.!\n!"), (",!TAPE.", "\n!"));
+        assert_eq!(Parser::split_input("!TAPE"), ("!TAPE", ""));
+        assert_eq!(Parser::split_input("😎!ą"), ("😎", "ą"));
+    }
+
     #[test]
     fn test_check_brackets() {
         assert_eq!(Parser::check_brackets("[]"), Ok(()));
@@ -0,0 +1,175 @@
+//! Ook! is brainfuck for orang-utans, where every operation is a pair of
+//! `Ook.`, `Ook?` or `Ook!` words, e.g. `Ook. Ook?` is `>`.
+
+use super::{instruction::Operation, token::Token, utils::Position, Parser, Program};
+
+const OOKS: [(char, char, char); 8] = [
+    ('.', '?', '>'),
+    ('?', '.', '<'),
+    ('.', '.', '+'),
+    ('!', '!', '-'),
+    ('!', '.', '.'),
+    ('.', '!', ','),
+    ('!', '?', '['),
+    ('?', '!', ']'),
+];
+
+fn operation(first: char, second: char) -> Option<Operation> {
+    let (_, _, bf) = OOKS.iter().find(|(a, b, _)| *a == first && *b == second)?;
+    Operation::from_symbol(*bf)
+}
+
+/// Splits Ook! source code into operations, positioned at their first Ook, and comments.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut pos = Position {
+        line_number: 0,
+        char_number: 0,
+    };
+    let mut comment = String::new();
+    let mut comment_pos = pos;
+    // first Ook of a pair that is currently being read
+    let mut first_ook: Option<(char, Position)> = None;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\r' || c == '\n' {
+            if c == '\r' && i + 1 < chars.len() && chars[i + 1] == '\n' {
+                i += 1;
+            }
+            Parser::push_comment(&mut tokens, &mut comment, comment_pos);
+            pos.line_number += 1;
+            pos.char_number = 0;
+            i += 1;
+            continue;
+        }
+
+        if i + 3 < chars.len() && chars[i..i + 3] == ['O', 'o', 'k'] && ".?!".contains(chars[i + 3])
+        {
+            Parser::push_comment(&mut tokens, &mut comment, comment_pos);
+            let punctuation = chars[i + 3];
+            match first_ook.take() {
+                None => first_ook = Some((punctuation, pos)),
+                Some((first, first_pos)) => match operation(first, punctuation) {
+                    Some(op) => tokens.push(Token::Op(op, first_pos)),
+                    None => {
+                        return Err(format!(
+                            "Invalid pair `Ook{} Ook{}` found at {}",
+                            first, punctuation, first_pos
+                        ))
+                    }
+                },
+            }
+            pos.char_number += 4;
+            i += 4;
+            continue;
+        }
+
+        if !comment.is_empty() || !c.is_whitespace() {
+            if comment.is_empty() {
+                comment_pos = pos;
+            }
+            comment.push(c);
+        }
+        pos.char_number += 1;
+        i += 1;
+    }
+    Parser::push_comment(&mut tokens, &mut comment, comment_pos);
+
+    match first_ook {
+        Some((_, pos)) => Err(format!("Ook without a pair found at {}", pos)),
+        None => Ok(tokens),
+    }
+}
+
+/// How the operation is written in Ook!, if it's one of the 8 brainfuck ones.
+pub fn word(op: &Operation) -> Option<String> {
+    let (first, second, _) = OOKS
+        .iter()
+        .find(|(_, _, bf)| Operation::from_symbol(*bf).is_some_and(|o| o.same_kind(op)))?;
+    Some(format!("Ook{} Ook{}", first, second))
+}
+
+pub fn parse(src: &str) -> Result<Program, String> {
+    Parser::from_tokens(&tokenize(src)?)
+}
+
+#[cfg(test)]
+mod ook_tests {
+    use super::*;
+    use crate::parser::dialect::Dialect;
+
+    #[test]
+    fn test_operations() {
+        let p = parse(
+            "Ook. Ook? Ook? Ook. Ook. Ook. Ook! Ook! Ook! Ook. Ook. Ook! Ook! Ook? Ook? Ook!",
+        )
+        .unwrap();
+        assert_eq!(p.to_brainfuck(), "><+-.,[]");
+    }
+
+    #[test]
+    fn test_positions() {
+        let p = parse("Ook. Ook.\n  Ook! Ook.").unwrap();
+        assert_eq!(
+            p.instructions()[1].get_position(),
+            &Position {
+                line_number: 1,
+                char_number: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            tokenize("Hi Ook. there Ook.").unwrap(),
+            vec![
+                Token::Comment(
+                    "Hi".to_string(),
+                    Position {
+                        line_number: 0,
+                        char_number: 0
+                    }
+                ),
+                Token::Comment(
+                    "there".to_string(),
+                    Position {
+                        line_number: 0,
+                        char_number: 8
+                    }
+                ),
+                Token::Op(
+                    Operation::CellInc,
+                    Position {
+                        line_number: 0,
+                        char_number: 3
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("Ook. Ook. Ook.").is_err());
+        assert!(parse("Ook? Ook?").is_err());
+        let e = parse("Ook. Ook.\nOok? Ook!").unwrap_err();
+        assert!(e.contains("line 2, char 1"), "{}", e);
+    }
+
+    #[test]
+    fn test_translation_roundtrip() {
+        let bf = "++++++++[>++++++++<-]>+.,";
+        let ook = Dialect::Brainfuck
+            .translate(bf, &Dialect::Ook, true)
+            .unwrap();
+        assert_eq!(ook.matches("Ook").count(), 2 * bf.len());
+        assert_eq!(
+            Dialect::Ook.translate(&ook, &Dialect::Brainfuck, true),
+            Ok(bf.to_string() + "\n")
+        );
+    }
+}
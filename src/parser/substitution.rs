@@ -0,0 +1,235 @@
+//! Many joke languages are just brainfuck with its 8 operations written differently,
+//! such dialects are described by files like this one:
+//! ```toml
+//! name = "alphuck"
+//! ">" = "a"
+//! "<" = "c"
+//! "+" = "e"
+//! "-" = "i"
+//! "." = "j"
+//! "," = "o"
+//! "[" = "p"
+//! "]" = "s"
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use super::{instruction::Operation, token::Token, utils::Position, Parser, Program};
+
+const BUILTINS: [&str; 3] = [
+    include_str!("dialects/alphuck.toml"),
+    include_str!("dialects/pikalang.toml"),
+    include_str!("dialects/blub.toml"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubstitutionDialect {
+    name: String,
+    /// how each operation is written, sorted from the longest
+    /// so that tokenizing can just take the first one that matches
+    words: Vec<(String, Operation)>,
+}
+
+impl SubstitutionDialect {
+    /// Reads a dialect from its TOML description.
+    pub fn from_toml(src: &str) -> Result<Self, String> {
+        let table = src
+            .parse::<toml::Value>()
+            .map_err(|e| format!("Invalid dialect definition: {}", e))?;
+        let table = table
+            .as_table()
+            .ok_or_else(|| "Dialect definition has to be a table".to_string())?;
+
+        let name = match table.get("name") {
+            Some(toml::Value::String(name)) => name.clone(),
+            _ => return Err("Dialect definition is missing its name".to_string()),
+        };
+
+        let mut words = Vec::new();
+        for symbol in "><+-.,[]".chars() {
+            let word = match table.get(&symbol.to_string()) {
+                Some(toml::Value::String(word)) => word,
+                _ => return Err(format!("Dialect {} doesn't define `{}`", name, symbol)),
+            };
+            if word.is_empty() || word.contains(['\n', '\r']) {
+                return Err(format!(
+                    "Dialect {} defines `{}` as an empty or multiline word",
+                    name, symbol
+                ));
+            }
+            if words.iter().any(|(w, _)| w == word) {
+                return Err(format!("Dialect {} uses `{}` more than once", name, word));
+            }
+            let op = Operation::from_symbol(symbol).expect("all 8 symbols are operations");
+            words.push((word.clone(), op));
+        }
+        words.sort_by_key(|(word, _)| std::cmp::Reverse(word.chars().count()));
+
+        Ok(Self { name, words })
+    }
+
+    pub fn load<P: AsRef<Path>>(file: P) -> Result<Self, String> {
+        let src = fs::read_to_string(file.as_ref()).map_err(|e| {
+            format!(
+                "Error occured while reading {}: {}",
+                file.as_ref().display(),
+                e
+            )
+        })?;
+        Self::from_toml(&src)
+    }
+
+    pub fn builtins() -> Vec<Self> {
+        BUILTINS
+            .iter()
+            .map(|src| Self::from_toml(src).expect("builtin dialects are valid"))
+            .collect()
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        Self::builtins().into_iter().find(|d| d.name == name)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Splits src into operations and comments,
+    /// always taking the longest word that matches.
+    pub fn tokenize(&self, src: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut pos = Position {
+            line_number: 0,
+            char_number: 0,
+        };
+        let mut comment = String::new();
+        let mut comment_pos = pos;
+
+        let mut rest = src;
+        while let Some(c) = rest.chars().next() {
+            if c == '\r' || c == '\n' {
+                rest = rest.strip_prefix("\r\n").unwrap_or(&rest[c.len_utf8()..]);
+                Parser::push_comment(&mut tokens, &mut comment, comment_pos);
+                pos.line_number += 1;
+                pos.char_number = 0;
+                continue;
+            }
+
+            if let Some((word, op)) = self
+                .words
+                .iter()
+                .find(|(w, _)| rest.starts_with(w.as_str()))
+            {
+                Parser::push_comment(&mut tokens, &mut comment, comment_pos);
+                tokens.push(Token::Op(op.clone(), pos));
+                pos.char_number += word.chars().count();
+                rest = &rest[word.len()..];
+                continue;
+            }
+
+            if !comment.is_empty() || !c.is_whitespace() {
+                if comment.is_empty() {
+                    comment_pos = pos;
+                }
+                comment.push(c);
+            }
+            pos.char_number += 1;
+            rest = &rest[c.len_utf8()..];
+        }
+        Parser::push_comment(&mut tokens, &mut comment, comment_pos);
+
+        tokens
+    }
+
+    pub fn parse(&self, src: &str) -> Result<Program, String> {
+        Parser::from_tokens(&self.tokenize(src))
+    }
+
+    /// How the operation is written in this dialect, if it's one of the 8 brainfuck ones.
+    pub fn word(&self, op: &Operation) -> Option<&str> {
+        self.words
+            .iter()
+            .find(|(_, o)| o.same_kind(op))
+            .map(|(w, _)| w.as_str())
+    }
+
+    /// Number of characters of the longest word.
+    pub fn longest_word(&self) -> usize {
+        self.words
+            .iter()
+            .map(|(w, _)| w.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod substitution_tests {
+    use super::*;
+    use crate::parser::dialect::Dialect;
+
+    #[test]
+    fn test_builtins() {
+        let names = SubstitutionDialect::builtins()
+            .iter()
+            .map(|d| d.get_name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["alphuck", "pikalang", "blub"]);
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let pikalang = SubstitutionDialect::builtin("pikalang").unwrap();
+        let p = pikalang.parse("pi pipi pikachu pika pikapi chu").unwrap();
+        assert_eq!(p.to_brainfuck(), "+>.[,]");
+        let p = pikalang.parse("pipipi").unwrap();
+        assert_eq!(p.to_brainfuck(), ">+");
+    }
+
+    #[test]
+    fn test_positions_and_comments() {
+        let alphuck = SubstitutionDialect::builtin("alphuck").unwrap();
+        let tokens = alphuck.tokenize("ee\n X a");
+        assert_eq!(
+            tokens[1],
+            Token::Op(
+                Operation::CellInc,
+                Position {
+                    line_number: 0,
+                    char_number: 1
+                }
+            )
+        );
+        assert_eq!(
+            tokens[2],
+            Token::Comment(
+                "X".to_string(),
+                Position {
+                    line_number: 1,
+                    char_number: 1
+                }
+            )
+        );
+        let e = alphuck.parse("\n  p").unwrap_err();
+        assert!(e.contains("line 2, char 3"), "{}", e);
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        assert!(SubstitutionDialect::from_toml("").is_err());
+        assert!(SubstitutionDialect::from_toml("name = \"x\"\n\">\" = \"a\"").is_err());
+        let duplicate = include_str!("dialects/alphuck.toml").replace("\"c\"", "\"a\"");
+        assert!(SubstitutionDialect::from_toml(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_translation_roundtrip() {
+        for dialect in SubstitutionDialect::builtins() {
+            let src = "++[>+<-]>.,";
+            let dialect = Dialect::Substitution(dialect);
+            let translated = Dialect::Brainfuck.translate(src, &dialect, true).unwrap();
+            assert_eq!(dialect.parse(&translated).unwrap().to_brainfuck(), src);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use super::{instruction::Operation, utils::Position};
+
+/// Smallest meaningful piece of source code, which all the dialects are split into
+/// before being turned into a `Program`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Op(Operation, Position),
+    /// Anything that isn't an operation, without the surrounding whitespace.
+    Comment(String, Position),
+}
+
+impl Token {
+    pub fn get_position(&self) -> &Position {
+        match self {
+            Token::Op(_, pos) | Token::Comment(_, pos) => pos,
+        }
+    }
+}
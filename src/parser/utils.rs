@@ -34,16 +34,22 @@ impl Display for Position {
 pub enum BracketCountMismatch {
     MoreOpening(Position),
     MoreClosing(Position),
+    /// Closing bracket (at the second position) is of a different kind than
+    /// the last opened one (at the first position), e.g. `[)`.
+    Interleaved(Position, Position),
 }
 
 impl Display for BracketCountMismatch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (bracket, pos) = if let BracketCountMismatch::MoreOpening(pos) = self {
-            ("opening", pos)
-        } else if let BracketCountMismatch::MoreClosing(pos) = self {
-            ("closing", pos)
-        } else {
-            unreachable!();
+        let (bracket, pos) = match self {
+            BracketCountMismatch::MoreOpening(pos) => ("opening", pos),
+            BracketCountMismatch::MoreClosing(pos) => ("closing", pos),
+            BracketCountMismatch::Interleaved(opening, closing) => {
+                return f.write_fmt(format_args!(
+                    "Bracket mismatch! Bracket closed at {} doesn't match the one opened at {}",
+                    closing, opening
+                ))
+            }
         };
         f.write_fmt(format_args!(
             "Bracket count mismatch! Extra {} bracket found at {}",
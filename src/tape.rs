@@ -4,51 +4,198 @@ use std::num::Wrapping;
 // TODO: create a BigIntTape and CharTape, both of which somehow share this code
 // by having this as implementation of a trait Tape
 
+/// Size of a single cell, values will wrap around when going outside of its range.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CellType {
+    /// Single bit, as in Boolfuck and Smallfuck.
+    Bit,
+    U8,
+    U16,
+    U32,
+    #[default]
+    Usize,
+}
+
+impl CellType {
+    pub fn max_value(&self) -> usize {
+        match self {
+            CellType::Bit => 1,
+            CellType::U8 => u8::MAX as usize,
+            CellType::U16 => u16::MAX as usize,
+            CellType::U32 => u32::MAX as usize,
+            CellType::Usize => usize::MAX,
+        }
+    }
+}
+
+/// What happens when the pointer goes beyond the ends of the tape.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TapeMode {
+    /// Tape grows to the right as needed, going to the left of the first cell is an error.
+    #[default]
+    Unbounded,
+    /// Tape has this many cells (but at least one) and going past either of its ends
+    /// wraps around to the other one.
+    Wrapping(usize),
+    /// Tape grows in both directions as needed, as in Boolfuck.
+    /// Going to the left of the first cell adds cells in front of it,
+    /// so positions of all the cells move to the right.
+    Infinite,
+}
+
 pub struct Tape {
     pub current_position: usize,
     pub current_value: Wrapping<usize>,
     tape: Vec<Wrapping<usize>>,
+    cell_type: CellType,
+    mode: TapeMode,
 }
 
 impl Default for Tape {
     fn default() -> Self {
+        Self::new(CellType::default(), TapeMode::default())
+    }
+}
+
+impl Tape {
+    pub fn new(cell_type: CellType, mode: TapeMode) -> Self {
+        let mode = match mode {
+            TapeMode::Wrapping(0) => TapeMode::Wrapping(1),
+            mode => mode,
+        };
         Self {
             current_position: 0,
             current_value: Wrapping(0),
             tape: vec![Wrapping(0)],
+            cell_type,
+            mode,
         }
     }
-}
 
-impl Tape {
+    /// Replaces the contents of the tape, e.g. with ones previously taken from `cells`,
+    /// will return an Error if the pointer or the cells don't fit on a wrapping tape.
+    pub fn restore_cells(
+        &mut self,
+        cells: Vec<Wrapping<usize>>,
+        position: usize,
+    ) -> Result<(), String> {
+        self.check_position(position)?;
+        if let TapeMode::Wrapping(length) = self.mode {
+            if cells.len() > length {
+                return Err(format!("Tape has only {} cells", length));
+            }
+        }
+        self.tape = if cells.is_empty() {
+            vec![Wrapping(0)]
+        } else {
+            cells
+        };
+        self.set_position(position);
+        Ok(())
+    }
+
+    /// Makes sure that the pointer can be at the given cell, which only isn't the case
+    /// past the end of a wrapping tape.
+    pub fn check_position(&self, position: usize) -> Result<(), String> {
+        match self.mode {
+            TapeMode::Wrapping(length) if position >= length => Err(format!(
+                "Cell {} is outside of the tape with {} cells",
+                position, length
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Puts the pointer at the given cell, e.g. when switching to another brainfork thread.
+    pub fn set_position(&mut self, position: usize) {
+        self.current_position = position;
+        self.current_value = self.tape.get(position).copied().unwrap_or(Wrapping(0));
+    }
+
+    pub fn get_cell_type(&self) -> CellType {
+        self.cell_type
+    }
+
+    /// All the cells that were created so far.
+    pub fn cells(&self) -> &[Wrapping<usize>] {
+        &self.tape
+    }
+
+    /// Whether the tape is still as it was created, with the pointer at the first cell.
+    pub fn is_blank(&self) -> bool {
+        self.current_position == 0 && self.tape.iter().all(|cell| cell.0 == 0)
+    }
+
     pub fn set_current_value(&mut self, value: Wrapping<usize>) {
+        // cells are only created once something is written to them,
+        // so that moving far to the right doesn't allocate the whole way there
+        if self.current_position >= self.tape.len() {
+            self.tape.resize(self.current_position + 1, Wrapping(0));
+        }
+        let value = Wrapping(value.0 & self.cell_type.max_value());
         self.tape[self.current_position] = value;
         self.current_value = value;
     }
 
-    pub fn move_right(&mut self, n: usize) -> Result<(), String> {
-        if self.current_position == usize::MAX {
-            return Err("Exceeded tape length".to_string());
-        }
-
-        self.current_position += n;
-        match self.tape.get(self.current_position) {
-            Some(v) => self.current_value = *v,
-            None => {
-                self.current_value = Wrapping(usize::MIN);
-                self.tape.push(self.current_value);
+    /// Cell the pointer would end up at after moving `n` cells to the right,
+    /// or to the left if `right` is false.
+    pub fn destination(&self, n: usize, right: bool) -> Result<usize, String> {
+        match (self.mode, right) {
+            (TapeMode::Unbounded, true) => self
+                .current_position
+                .checked_add(n)
+                .ok_or_else(|| "Exceeded tape length".to_string()),
+            (TapeMode::Unbounded, false) => self
+                .current_position
+                .checked_sub(n)
+                .ok_or_else(|| "Tried to go to the negative side of the tape".to_string()),
+            (TapeMode::Infinite, true) => self
+                .current_position
+                .checked_add(n)
+                .ok_or_else(|| "Exceeded tape length".to_string()),
+            // cells which are missing on the left will be added before moving there
+            (TapeMode::Infinite, false) => Ok(self.current_position.saturating_sub(n)),
+            // position + n could overflow on a long tape, so the end of the tape is crossed by subtracting
+            (TapeMode::Wrapping(length), true) => {
+                let (position, n) = (self.current_position % length, n % length);
+                Ok(if position >= length - n {
+                    position - (length - n)
+                } else {
+                    position + n
+                })
+            }
+            (TapeMode::Wrapping(length), false) => {
+                let (position, n) = (self.current_position % length, n % length);
+                Ok(if position >= n {
+                    position - n
+                } else {
+                    position + (length - n)
+                })
             }
         }
+    }
+
+    pub fn move_right(&mut self, n: usize) -> Result<(), String> {
+        let position = self.destination(n, true)?;
+        self.set_position(position);
         Ok(())
     }
 
-    pub fn move_left(&mut self, n: usize) -> Result<(), String> {
-        if self.current_position == usize::MIN {
-            return Err("Tried to go to the negative side of the tape".to_string());
+    /// Number of cells that will be added in front of the first one
+    /// when moving `n` cells to the left, which only happens on an infinite tape.
+    pub fn left_growth(&self, n: usize) -> usize {
+        match self.mode {
+            TapeMode::Infinite => n.saturating_sub(self.current_position),
+            _ => 0,
         }
+    }
 
-        self.current_position -= n;
-        self.current_value = *self.tape.get(self.current_position).unwrap();
+    pub fn move_left(&mut self, n: usize) -> Result<(), String> {
+        let position = self.destination(n, false)?;
+        let growth = self.left_growth(n);
+        self.tape
+            .splice(0..0, std::iter::repeat(Wrapping(0)).take(growth));
+        self.set_position(position);
         Ok(())
     }
 
@@ -70,7 +217,11 @@ impl fmt::Display for Tape {
         let mut tape = String::new();
 
         for i in down_range..up_range {
-            let value = self.tape.get(i);
+            // cells between the last created one and the current one are all zeros
+            let value = match self.tape.get(i) {
+                None if i <= self.current_position => Some(&Wrapping(0)),
+                value => value,
+            };
             match value {
                 Some(v) => {
                     if i == self.current_position {
@@ -87,7 +238,7 @@ impl fmt::Display for Tape {
         }
         // if we are on the last created cell
         // we don't want to print "..." as if there is something further
-        if self.current_position != self.tape.len() - 1 && up_range < usize::MAX {
+        if self.current_position + 1 < self.tape.len() && up_range < usize::MAX {
             tape = format!("{}...", tape);
         }
         write!(f, "{}", tape)
@@ -109,6 +260,74 @@ mod test_tape {
         assert_ne!(tape.move_right(1), Ok(()));
     }
 
+    #[test]
+    fn test_moving_by_more_than_one() {
+        let mut tape = Tape::default();
+        assert_eq!(tape.move_right(3), Ok(()));
+        tape.inc(1);
+        assert_eq!(tape.move_left(2), Ok(()));
+        assert_eq!(tape.current_value, Wrapping(0));
+        assert_ne!(tape.move_left(2), Ok(()));
+        assert_eq!(tape.move_right(2), Ok(()));
+        assert_eq!(tape.current_value, Wrapping(1));
+    }
+
+    #[test]
+    fn test_wrapping_tape() {
+        let mut tape = Tape::new(CellType::Usize, TapeMode::Wrapping(3));
+        assert_eq!(tape.move_left(1), Ok(()));
+        assert_eq!(tape.current_position, 2);
+        tape.inc(7);
+        assert_eq!(tape.move_right(4), Ok(()));
+        assert_eq!(tape.current_position, 0);
+        assert_eq!(tape.move_right(2), Ok(()));
+        assert_eq!(tape.current_value, Wrapping(7));
+
+        let mut tape = Tape::new(CellType::Usize, TapeMode::Wrapping(0));
+        assert_eq!(tape.move_right(5), Ok(()));
+        assert_eq!(tape.current_position, 0);
+
+        // going around a tape longer than half of the address space
+        let length = usize::MAX - 1;
+        let mut tape = Tape::new(CellType::Usize, TapeMode::Wrapping(length));
+        assert_eq!(tape.destination(length - 1, true), Ok(length - 1));
+        assert_eq!(tape.move_left(1), Ok(()));
+        assert_eq!(tape.destination(length - 1, true), Ok(length - 2));
+        assert_eq!(tape.destination(usize::MAX, false), Ok(length - 2));
+    }
+
+    #[test]
+    fn test_infinite_tape() {
+        let mut tape = Tape::new(CellType::Bit, TapeMode::Infinite);
+        tape.inc(1);
+        assert_eq!(tape.move_left(2), Ok(()));
+        assert_eq!(tape.current_position, 0);
+        tape.inc(1);
+        assert_eq!(tape.cells(), &[Wrapping(1), Wrapping(0), Wrapping(1)]);
+        assert_eq!(tape.move_right(2), Ok(()));
+        assert_eq!(tape.current_value, Wrapping(1));
+        assert_eq!(tape.left_growth(3), 1);
+    }
+
+    #[test]
+    fn test_cell_types() {
+        let mut tape = Tape::new(CellType::U8, TapeMode::Unbounded);
+        tape.dec(1);
+        assert_eq!(tape.current_value, Wrapping(255));
+        tape.inc(2);
+        assert_eq!(tape.current_value, Wrapping(1));
+
+        let mut tape = Tape::new(CellType::U16, TapeMode::Unbounded);
+        tape.dec(1);
+        assert_eq!(tape.current_value, Wrapping(u16::MAX as usize));
+
+        let mut tape = Tape::new(CellType::Bit, TapeMode::Unbounded);
+        tape.inc(1);
+        assert_eq!(tape.current_value, Wrapping(1));
+        tape.inc(1);
+        assert_eq!(tape.current_value, Wrapping(0));
+    }
+
     #[test]
     fn test_current_value() {
         let mut tape = Tape::default();
@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use termion::raw::{IntoRawMode, RawTerminal};
+
+const CTRL_C: u8 = 3;
+const CTRL_D: u8 = 4;
+
+/// Stdin in raw mode, so that every keypress is delivered immediately,
+/// without waiting for enter, just like `getchar` in C.
+///
+/// Keys are read by a separate thread, which restores the terminal as soon as Ctrl-C
+/// is pressed, even when the program isn't waiting for input (in raw mode Ctrl-C doesn't
+/// send SIGINT), so that pressing it again stops the program as usual.
+/// The next read then fails with `ErrorKind::Interrupted`, so that the program
+/// stops after writing out everything it printed.
+/// Terminal is also restored when this is dropped. Ctrl-D ends the input.
+pub struct RawInput {
+    keys: Receiver<io::Result<u8>>,
+    interrupted: bool,
+    terminal: Arc<Mutex<Option<RawTerminal<File>>>>,
+}
+
+impl RawInput {
+    pub fn new() -> io::Result<Self> {
+        // the terminal itself rather than stdout, which may be redirected to a file
+        let terminal = Arc::new(Mutex::new(Some(termion::get_tty()?.into_raw_mode()?)));
+        let (sender, keys) = mpsc::channel();
+
+        let terminal_to_restore = Arc::clone(&terminal);
+        thread::spawn(move || {
+            for key in io::stdin().lock().bytes() {
+                match key {
+                    Ok(CTRL_C) => {
+                        // dropping RawTerminal restores the previous terminal state
+                        if let Ok(mut terminal) = terminal_to_restore.lock() {
+                            terminal.take();
+                        }
+                        let _ = sender.send(Err(io::Error::new(
+                            ErrorKind::Interrupted,
+                            "Interrupted with Ctrl-C",
+                        )));
+                        break;
+                    }
+                    Ok(CTRL_D) | Err(_) => break,
+                    Ok(key) => {
+                        if sender.send(Ok(key)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            keys,
+            interrupted: false,
+            terminal,
+        })
+    }
+}
+
+impl Read for RawInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.interrupted {
+            return Err(io::Error::new(
+                ErrorKind::Interrupted,
+                "Interrupted with Ctrl-C",
+            ));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // whatever the program printed so far is probably a prompt for this input
+        io::stdout().flush()?;
+        match self.keys.recv() {
+            Ok(Ok(key)) => {
+                buf[0] = key;
+                Ok(1)
+            }
+            Ok(Err(e)) => {
+                self.interrupted = true;
+                Err(e)
+            }
+            // the reading thread has stopped, so there won't be any more input
+            Err(_) => Ok(0),
+        }
+    }
+}
+
+impl Drop for RawInput {
+    fn drop(&mut self) {
+        if let Ok(mut terminal) = self.terminal.lock() {
+            terminal.take();
+        }
+    }
+}
+
+/// In raw mode the terminal no longer moves the cursor to the beginning of the line
+/// on `\n`, so this writer adds `\r` before every `\n`.
+pub struct RawOutput<W: Write>(pub W);
+
+impl<W: Write> Write for RawOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        for line in buf.split_inclusive(|b| *b == b'\n') {
+            match line.split_last() {
+                Some((b'\n', rest)) => {
+                    self.0.write_all(rest)?;
+                    self.0.write_all(b"\r\n")?;
+                }
+                _ => self.0.write_all(line)?,
+            }
+            written += line.len();
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod terminal_tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_output() {
+        let mut output = RawOutput(Vec::new());
+        assert_eq!(output.write(b"a\nb\n\nc").unwrap(), 6);
+        assert_eq!(output.0, b"a\r\nb\r\n\r\nc");
+    }
+}
@@ -0,0 +1,226 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::interpreter::{coverage::Coverage, options::Options, Interpreter};
+use crate::parser::dialect::Dialect;
+
+/// Program together with the output it's expected to print for its input.
+/// The expected output is either in a file next to the program with the same name
+/// and the `.out` extension, or in its first line written as `[expected: output]` (with `\n`
+/// for newlines), which is a loop that never runs. Input is taken from the `.in` file,
+/// if there is none the program gets nothing to read.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TestCase {
+    pub path: PathBuf,
+    pub dialect: Dialect,
+    pub src: String,
+    pub input: Vec<u8>,
+    pub expected: Vec<u8>,
+}
+
+/// What happened when the program was run.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Outcome {
+    Passed,
+    /// it printed something else, which is kept here
+    WrongOutput(Vec<u8>),
+    Error(String),
+}
+
+impl TestCase {
+    /// Reads the program and its expectations, returns None if it doesn't have any.
+    /// The program is written in the given dialect, or if there is none in the one
+    /// recognized by its extension, otherwise it's also None.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        dialect: Option<&Dialect>,
+    ) -> Result<Option<Self>, String> {
+        let path = path.as_ref();
+        let dialect = match dialect.cloned().or_else(|| Dialect::from_path(path)) {
+            Some(dialect) => dialect,
+            None => return Ok(None),
+        };
+        let read = |path: &Path| {
+            fs::read(path)
+                .map_err(|e| format!("Error occured while reading {}: {}", path.display(), e))
+        };
+        let src = String::from_utf8(read(path)?)
+            .map_err(|_| format!("{} is not valid UTF-8", path.display()))?;
+
+        let expected = match path.with_extension("out") {
+            out if out.is_file() => read(&out)?,
+            _ => match header(&src) {
+                Some(expected) => expected,
+                None => return Ok(None),
+            },
+        };
+        let input = match path.with_extension("in") {
+            input if input.is_file() => read(&input)?,
+            _ => Vec::new(),
+        };
+
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            dialect,
+            src,
+            input,
+            expected,
+        }))
+    }
+
+    /// Runs the program with the given options,
+    /// optionally counting how many times each of its instructions was executed.
+    pub fn run(&self, options: Options, coverage: bool) -> (Outcome, Option<Coverage>) {
+        let mut interpreter =
+            Interpreter::with_options(Cursor::new(self.input.clone()), Vec::new(), options);
+        if coverage {
+            interpreter.record_coverage();
+        }
+        let result = interpreter.run_dialect_source(
+            &self.src,
+            &self.path.display().to_string(),
+            &self.dialect,
+        );
+        let coverage = interpreter.take_coverage();
+        let outcome = match result {
+            Err(e) => Outcome::Error(e),
+            Ok(()) if interpreter.output == self.expected => Outcome::Passed,
+            Ok(()) => Outcome::WrongOutput(interpreter.output),
+        };
+        (outcome, coverage)
+    }
+}
+
+/// Expected output written in the first line of the program as `[expected: output]`.
+fn header(src: &str) -> Option<Vec<u8>> {
+    let line = src.lines().next()?;
+    let expected = line.strip_prefix("[expected: ")?.strip_suffix(']')?;
+    Some(expected.replace("\\n", "\n").into_bytes())
+}
+
+/// Finds all the test cases in the directory and its subdirectories, sorted by their paths.
+/// A path to a single program gives just that one.
+/// Programs in directories are recognized by their extensions, unless they are run
+/// in the given dialect, in which case every file except `.in` and `.out` ones is a program.
+pub fn discover<P: AsRef<Path>>(
+    path: P,
+    dialect: Option<&Dialect>,
+) -> Result<Vec<TestCase>, String> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(TestCase::load(path, dialect)?.into_iter().collect());
+    }
+
+    let mut entries = fs::read_dir(path)
+        .and_then(|dir| {
+            dir.map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| format!("Error occured while reading {}: {}", path.display(), e))?;
+    entries.sort();
+
+    let mut cases = Vec::new();
+    for entry in entries {
+        let program = match dialect {
+            Some(_) => !matches!(
+                entry.extension().and_then(|e| e.to_str()),
+                Some("in") | Some("out")
+            ),
+            None => Dialect::from_path(&entry).is_some(),
+        };
+        if entry.is_dir() || program {
+            cases.extend(discover(entry, dialect)?);
+        }
+    }
+    Ok(cases)
+}
+
+/// Lines which have to be removed (`-`) from the expected output and added (`+`) to it
+/// to get the actual one, surrounded by the lines that are the same in both (` `).
+pub fn diff(expected: &str, actual: &str) -> Vec<(char, String)> {
+    let expected = expected.split('\n').collect::<Vec<_>>();
+    let actual = actual.split('\n').collect::<Vec<_>>();
+
+    // lengths of the longest common subsequences of the suffixes
+    let mut lcs = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((' ', expected[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', expected[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(('+', actual[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod testing_tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        assert_eq!(
+            header("[expected: Hello World!\\n]\n+."),
+            Some(b"Hello World!\n".to_vec())
+        );
+        assert_eq!(header("[expected: ]"), Some(Vec::new()));
+        assert_eq!(header("+[-]"), None);
+        assert_eq!(header("[-]\n+."), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut case = TestCase {
+            path: PathBuf::from("echo.bf"),
+            dialect: Dialect::Brainfuck,
+            src: ",[.,]".to_string(),
+            input: b"hi\0".to_vec(),
+            expected: b"hi".to_vec(),
+        };
+        assert_eq!(case.run(Options::default(), false), (Outcome::Passed, None));
+
+        case.input = b"ho\0".to_vec();
+        let (outcome, coverage) = case.run(Options::default(), true);
+        assert_eq!(outcome, Outcome::WrongOutput(b"ho".to_vec()));
+        assert_eq!(coverage.unwrap().hits(2), 2);
+
+        case.input = Vec::new();
+        assert!(matches!(
+            case.run(Options::default(), false).0,
+            Outcome::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nx\nc\nd"),
+            vec![
+                (' ', "a".to_string()),
+                ('-', "b".to_string()),
+                ('+', "x".to_string()),
+                (' ', "c".to_string()),
+                ('+', "d".to_string()),
+            ]
+        );
+    }
+}
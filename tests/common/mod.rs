@@ -0,0 +1,113 @@
+//! Support for differential testing: random programs and all the ways of running them,
+//! which have to end up in exactly the same state as the plain interpreter.
+
+use std::io::{Cursor, Read};
+
+use proptest::prelude::*;
+
+use biir::interpreter::{
+    folding::Prefix,
+    limits::Limits,
+    options::{EofPolicy, Options, OutputMode},
+    Interpreter,
+};
+use biir::parser::{format, minify::minify, Parser, Program};
+use biir::tape::{CellType, TapeMode};
+
+/// Programs which run for longer than this are skipped, as they probably never finish.
+pub const MAX_STEPS: usize = 10_000;
+
+/// Options under which every program either finishes or runs out of steps,
+/// as both the cells and the tape wrap around and `,` at the end of the input gives 0.
+pub fn options() -> Options {
+    Options {
+        cell_type: CellType::U8,
+        eof_policy: EofPolicy::Zero,
+        tape_mode: TapeMode::Wrapping(16),
+        limits: Limits {
+            max_steps: Some(MAX_STEPS),
+            ..Limits::default()
+        },
+        output_mode: OutputMode::Byte,
+        ..Options::default()
+    }
+}
+
+/// Random program with balanced brackets, which may also contain whitespace and comments.
+pub fn program() -> impl Strategy<Value = String> {
+    let operation = prop::sample::select(vec!["+", "-", "<", ">", ".", ",", " ", "\n", "a"]);
+    operation
+        .prop_map(String::from)
+        .prop_recursive(4, 64, 8, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(|code| code.concat()),
+                prop::collection::vec(inner, 0..8).prop_map(|code| format!("[{}]", code.concat())),
+            ]
+        })
+}
+
+pub fn input() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 0..8)
+}
+
+/// Everything that can be observed once the program has finished.
+#[derive(Debug, PartialEq)]
+pub struct State {
+    pub output: Vec<u8>,
+    /// without the zeros at the end, since cells are only created once they are written to
+    pub tape: Vec<usize>,
+    pub position: usize,
+}
+
+/// Ways of running a program which should be indistinguishable from the plain interpreter.
+#[derive(Debug, Clone, Copy)]
+pub enum Variant {
+    Minified,
+    Formatted,
+    /// with everything before the first `,` executed ahead of time
+    Folded,
+}
+
+pub const VARIANTS: [Variant; 3] = [Variant::Minified, Variant::Formatted, Variant::Folded];
+
+/// Runs the program with the plain interpreter,
+/// returns None if it didn't finish within `MAX_STEPS`.
+pub fn run(src: &str, input: &[u8]) -> Option<State> {
+    let mut program = Parser::parse(src).unwrap();
+    finish(interpreter(input), &mut program)
+}
+
+pub fn run_variant(variant: Variant, src: &str, input: &[u8]) -> Option<State> {
+    match variant {
+        Variant::Minified => run(&minify(src).unwrap(), input),
+        Variant::Formatted => run(&format::format(src, format::DEFAULT_WIDTH).unwrap(), input),
+        Variant::Folded => {
+            let mut program = Parser::parse(src).unwrap();
+            let prefix = Prefix::evaluate(&mut program, options(), MAX_STEPS)?;
+            let mut interpreter = interpreter(input);
+            interpreter.apply_prefix(&mut program, &prefix).unwrap();
+            finish(interpreter, &mut program)
+        }
+    }
+}
+
+fn interpreter(input: &[u8]) -> Interpreter<Cursor<Vec<u8>>, Vec<u8>> {
+    Interpreter::with_options(Cursor::new(input.to_vec()), Vec::new(), options())
+}
+
+fn finish<R: Read>(
+    mut interpreter: Interpreter<R, Vec<u8>>,
+    program: &mut Program,
+) -> Option<State> {
+    interpreter.execute(program).ok()?;
+    let snapshot = interpreter.snapshot(program);
+    let mut tape = snapshot.tape;
+    while tape.last() == Some(&0) {
+        tape.pop();
+    }
+    Some(State {
+        output: interpreter.output,
+        tape,
+        position: snapshot.tape_position,
+    })
+}
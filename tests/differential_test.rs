@@ -0,0 +1,16 @@
+mod common;
+
+use proptest::prelude::*;
+
+use common::{input, program, run, run_variant, VARIANTS};
+
+proptest! {
+    #[test]
+    fn test_variants_match_interpreter(src in program(), input in input()) {
+        let expected = run(&src, &input);
+        prop_assume!(expected.is_some());
+        for variant in VARIANTS {
+            prop_assert_eq!(&run_variant(variant, &src, &input), &expected, "{:?}", variant);
+        }
+    }
+}
@@ -1,6 +1,51 @@
 #[cfg(test)]
 extern crate biir;
 
+use biir::interpreter::options::Options;
+use biir::parser::dialect::Dialect;
+use biir::parser::minify::minify;
+use biir::parser::substitution::SubstitutionDialect;
+use biir::testing::{discover, Outcome};
+
+#[test]
+fn test_example_programs() {
+    let cases = discover("./tests/programs", None).unwrap();
+    assert!(cases.iter().any(|c| c.path.ends_with("echo.bf")));
+    for case in cases {
+        let (outcome, _) = case.run(Options::default(), false);
+        assert_eq!(outcome, Outcome::Passed, "{}", case.path.display());
+    }
+}
+
+#[test]
+fn test_dialect_of_programs() {
+    let ook = Dialect::Ook;
+    let cases = discover("./tests/programs", Some(&ook)).unwrap();
+    assert!(cases.iter().all(|c| c.dialect == ook));
+    // .in and .out files aren't programs even then
+    assert!(cases.iter().all(|c| c.path.extension().unwrap() == "bf"));
+
+    // without a dialect only the extensions are known
+    assert_eq!(discover("./tests/alphuck", None).unwrap(), vec![]);
+    let alphuck = Dialect::Substitution(SubstitutionDialect::builtin("alphuck").unwrap());
+    let cases = discover("./tests/alphuck", Some(&alphuck)).unwrap();
+    assert_eq!(cases.len(), 1);
+    let (outcome, _) = cases[0].run(Options::default(), false);
+    assert_eq!(outcome, Outcome::Passed);
+}
+
+#[test]
+fn test_minified_programs() {
+    for mut case in discover("./tests/programs", None).unwrap() {
+        let minified = minify(&case.src).unwrap();
+        assert!(minified.len() < case.src.len(), "{}", case.path.display());
+
+        case.src = minified;
+        let (outcome, _) = case.run(Options::default(), false);
+        assert_eq!(outcome, Outcome::Passed, "{}", case.path.display());
+    }
+}
+
 // #[test_case( "!TAPE\n+!TAPE\n+!TAPE",
 //     r"(?m).*!TAPE.*: \[0\] \n.*!TAPE.*: \[1\] \n.*!TAPE.*: \[2\]" ; "!TAPE")]
 // fn test_output(program: &str, expected_output: &str) {
@@ -35,36 +80,6 @@ extern crate biir;
 //     assert!(expected_error_message.is_match(&error_msg));
 // }
 
-// #[test]
-// fn test_example_programs() {
-//     for f in fs::read_dir("./tests/programs").unwrap() {
-//         let f = f.unwrap();
-//         let program = f.path();
-//         test_program(program);
-//     }
-// }
-
-// fn test_program(program: std::path::PathBuf) {
-//     dbg!(program.file_name().unwrap());
-//     let mut interpreter = Interpreter::new(Modes {
-//         debug: true,
-//         numerical: false,
-//         big_int: false,
-//     });
-//     interpreter.output = Output::Vector(Vec::new());
-
-//     // expected output is in the first line
-//     let expected_output = fs::read_to_string(&program)
-//         .unwrap()
-//         .lines()
-//         .next()
-//         .unwrap()
-//         .replace("\\n", "\n");
-
-//     assert_eq!(interpreter.run(&program), Ok(()));
-//     assert_eq!(format!("[{}]", interpreter.output.read()), expected_output);
-// }
-
 // TODO: test program with newlines
 // TODO: test program with windows style (\r\n) newlines
 // TODO: test numerical mode (with big ints)
@@ -0,0 +1,6 @@
+[expected: AB]
+Output of a compiler full of operations which cancel out
+[this loop is never entered as the program has just started]
+++++++++[>++++++++<-]>+-+.<>+.
+[-][neither is this one]+-
+>><<
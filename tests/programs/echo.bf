@@ -0,0 +1,2 @@
+Prints back every line of the input (its expected output is in echo dot out)
+,----------[++++++++++.,----------]
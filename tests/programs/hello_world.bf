@@ -1,2 +1,2 @@
-[Hello World!\n]
+[expected: Hello World!\n]
 ++++++++++[>+++++++>++++++++++>+++>+<<<<-]>++.>+.+++++++..+++.>++.<<+++++++++++++++.>.+++.------.--------.>+.>.